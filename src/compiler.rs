@@ -1,7 +1,15 @@
 // src/compiler/compiler.rs
 
+use crate::linker::Module;
+use crate::vm::execution::ExecutionContext;
+use crate::vm::heap::Heap;
 use crate::vm::opcodes::OpCode;
+use crate::vm::symbol::Symbol;
 use crate::vm::value::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
 use thiserror::Error;
 
 #[derive(Debug, Error, Clone)]
@@ -12,144 +20,972 @@ pub enum CompilerError {
     InvalidAddress(String),
     #[error("Parse error: {0}")]
     ParseError(String),
+    #[error("Variable {0} loaded before it is stored")]
+    UndefinedVariable(usize),
+    #[error("Duplicate label: {0}")]
+    DuplicateLabel(String),
 }
 
 pub struct Compiler;
 
+/// Suspicious but legal code reported by [`Compiler::compile_with_diagnostics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilerWarning {
+    /// Bytecode index of the offending instruction.
+    pub ip: usize,
+    pub message: String,
+}
+
+/// Settings that relax how source is compiled. The default is strict.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompilerOptions {
+    /// Compile a bare word that is not a number, keyword, or opcode to a
+    /// string push, as if it had been quoted, instead of rejecting it as an
+    /// invalid token.
+    pub bare_word_strings: bool,
+    /// Evaluate straight-line runs of pure stack and arithmetic opcodes at
+    /// compile time, replacing each run with pushes of its results padded
+    /// with `Nop`s, so every address stays where it was. Runs that fail,
+    /// such as a division by zero, are kept for the VM to report.
+    pub evaluate_constants: bool,
+    /// Emit `JumpRel`, `JumpIfFalseRel`, and `JumpIfTrueRel` in place of
+    /// their absolute forms, so the jumps survive the bytecode being moved.
+    /// Jumps in a `Switch` table stay absolute.
+    pub relative_jumps: bool,
+}
+
+/// Bytecode together with the address execution starts from.
+#[derive(Debug, Clone)]
+pub struct CompiledProgram {
+    pub bytecode: Vec<OpCode>,
+    /// Set with the `entry` directive; `0` when the source has none.
+    pub entry: usize,
+}
+
 impl Compiler {
+    /// Compiles `source` to bytecode. An `entry` directive is validated but
+    /// otherwise dropped; use [`Compiler::compile_program`] to keep it.
+    ///
+    /// String and big integer literals and `Assert` messages are interned as
+    /// [`Symbol`]s, which live for the rest of the process; see there.
     pub fn compile(source: &str) -> Result<Vec<OpCode>, CompilerError> {
-        let mut bytecode = Vec::new();
-
-        let mut tokens = source.split_whitespace();
-        while let Some(token) = tokens.next() {
-            if token == "true" || token == "false" {
-                bytecode.push(OpCode::PushConst(Value::Boolean(token == "true")));
-            } else if token.contains('.') {
-                let num = token
-                    .parse::<f64>()
-                    .map_err(|_| CompilerError::ParseError(format!("Invalid float: {}", token)))?;
-                bytecode.push(OpCode::PushConst(Value::Float(num)));
-            } else if let Ok(num) = token.parse::<i32>() {
-                bytecode.push(OpCode::PushConst(Value::Integer(num)));
-            } else {
-                match token {
-                    "StoreVar" => {
-                        let index_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress(
-                                "expected variable index after StoreVar".into(),
-                            )
-                        })?;
-                        let index = index_token.parse::<usize>().map_err(|_| {
-                            CompilerError::InvalidAddress(index_token.to_string())
-                        })?;
-                        bytecode.push(OpCode::StoreVar(index));
-                    }
-                    "LoadVar" => {
-                        let index_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress(
-                                "expected variable index after LoadVar".into(),
-                            )
-                        })?;
-                        let index = index_token.parse::<usize>().map_err(|_| {
-                            CompilerError::InvalidAddress(index_token.to_string())
-                        })?;
-                        bytecode.push(OpCode::LoadVar(index));
-                    }
-                    "Pop" => bytecode.push(OpCode::Pop),
-                    "Dup" => bytecode.push(OpCode::Dup),
-                    "Swap" => bytecode.push(OpCode::Swap),
-                    "+" | "Add" => bytecode.push(OpCode::Add),
-                    "-" | "Sub" => bytecode.push(OpCode::Sub),
-                    "*" | "Mul" => bytecode.push(OpCode::Mul),
-                    "/" | "Div" => bytecode.push(OpCode::Div),
-                    "%" | "Mod" => bytecode.push(OpCode::Mod),
-                    "Neg" => bytecode.push(OpCode::Neg),
-                    "Exp" | "^" => bytecode.push(OpCode::Exp),
-                    "Jump" => {
-                        let addr_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress("expected address after Jump".into())
-                        })?;
-                        let addr = addr_token
-                            .parse::<usize>()
-                            .map_err(|_| CompilerError::InvalidAddress(addr_token.to_string()))?;
-                        bytecode.push(OpCode::Jump(addr));
-                    }
-                    "JumpIfFalse" => {
-                        let addr_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress(
-                                "expected address after JumpIfFalse".into(),
-                            )
-                        })?;
-                        let addr = addr_token
-                            .parse::<usize>()
-                            .map_err(|_| CompilerError::InvalidAddress(addr_token.to_string()))?;
-                        bytecode.push(OpCode::JumpIfFalse(addr));
-                    }
-                    "Call" => {
-                        let addr_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress("expected address after Call".into())
-                        })?;
-                        let addr = addr_token
-                            .parse::<usize>()
-                            .map_err(|_| CompilerError::InvalidAddress(addr_token.to_string()))?;
-                        bytecode.push(OpCode::Call(addr));
-                    }
-                    "SpawnActor" => {
-                        let addr_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress(
-                                "expected address after SpawnActor".into(),
-                            )
-                        })?;
-                        let addr = addr_token
-                            .parse::<usize>()
-                            .map_err(|_| CompilerError::InvalidAddress(addr_token.to_string()))?;
-                        bytecode.push(OpCode::SpawnActor(addr));
-                    }
-                    "SendMessage" => {
-                        bytecode.push(OpCode::SendMessage);
-                    }
-                    "ReceiveMessage" => {
-                        bytecode.push(OpCode::ReceiveMessage);
+        Self::compile_with_options(source, CompilerOptions::default())
+    }
+
+    /// Compiles `source` like [`Compiler::compile`] under the given options.
+    pub fn compile_with_options(
+        source: &str,
+        options: CompilerOptions,
+    ) -> Result<Vec<OpCode>, CompilerError> {
+        Ok(program(source, options)?.bytecode)
+    }
+
+    /// Compiles `source` together with its entry point, which an
+    /// `entry N` or `entry label` directive moves away from `0`.
+    pub fn compile_program(source: &str) -> Result<CompiledProgram, CompilerError> {
+        program(source, CompilerOptions::default())
+    }
+
+    /// Lazily compiles `source`, yielding each opcode as soon as its tokens
+    /// (including any operand) have been read.
+    ///
+    /// Labels are resolved as they are seen, so only backward references are
+    /// supported and a forward reference is reported as an invalid address.
+    /// An `entry` directive has no effect on the streamed opcodes.
+    /// Otherwise the iterator stops after the first error and collecting it
+    /// produces exactly the same result as [`Compiler::compile`].
+    pub fn compile_streaming(
+        source: &str,
+    ) -> impl Iterator<Item = Result<OpCode, CompilerError>> + '_ {
+        OpCodeStream {
+            items: Parser::new(source, CompilerOptions::default()),
+            labels: HashMap::new(),
+            position: 0,
+            failed: false,
+        }
+    }
+
+    /// Compiles `source` like [`Compiler::compile`], additionally rejecting any
+    /// `LoadVar` of an index that no earlier `StoreVar` has written.
+    ///
+    /// The check is a linear scan in instruction order and does not follow
+    /// jumps, so a program that stores a variable on a branch taken only at
+    /// runtime must store it before the load textually to pass.
+    pub fn compile_strict(source: &str) -> Result<Vec<OpCode>, CompilerError> {
+        let bytecode = Self::compile(source)?;
+        check_variables(&bytecode)?;
+        Ok(bytecode)
+    }
+
+    /// Compiles `source` like [`Compiler::compile`], additionally reporting
+    /// likely bugs that do not stop compilation: code that nothing can reach
+    /// after a `Return`, `Jump`, `TailCall`, or `Halt`, a `Jump` to itself, and
+    /// variables that are stored but never loaded.
+    pub fn compile_with_diagnostics(
+        source: &str,
+    ) -> Result<(Vec<OpCode>, Vec<CompilerWarning>), CompilerError> {
+        let program = program(source, CompilerOptions::default())?;
+        let warnings = lint(&program);
+        Ok((program.bytecode, warnings))
+    }
+
+    /// Compiles `source` as a module for the [`Linker`](crate::linker::Linker).
+    ///
+    /// Labels are exported unless their name starts with an underscore, and
+    /// references to labels the module does not define are left as imports
+    /// for the linker to resolve.
+    pub fn compile_module(name: &str, source: &str) -> Result<Module, CompilerError> {
+        let assembly = assemble(source, CompilerOptions::default())?;
+        Ok(Module {
+            name: name.to_string(),
+            bytecode: assembly.bytecode,
+            exports: assembly
+                .labels
+                .into_iter()
+                .filter(|(label, _)| !label.starts_with('_'))
+                .map(|(label, address)| (label.to_string(), address))
+                .collect(),
+            imports: assembly
+                .unresolved
+                .into_iter()
+                .map(|(ip, label)| (ip, label.to_string()))
+                .collect(),
+        })
+    }
+}
+
+/// Caches compiled bytecode by a hash of its source, for hosts that run the
+/// same scripts repeatedly.
+///
+/// The source is kept alongside its bytecode so a hash collision is treated
+/// as a miss rather than returning the wrong program. Failed compilations
+/// are not cached.
+#[derive(Debug, Default)]
+pub struct CompileCache {
+    entries: HashMap<u64, (String, Vec<OpCode>)>,
+    hits: usize,
+    misses: usize,
+}
+
+impl CompileCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the cached bytecode for `source`, compiling it with
+    /// [`Compiler::compile`] and caching the result on a miss.
+    pub fn get_or_compile(&mut self, source: &str) -> Result<Vec<OpCode>, CompilerError> {
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        let key = hasher.finish();
+        if let Some((cached, bytecode)) = self.entries.get(&key) {
+            if cached == source {
+                self.hits += 1;
+                return Ok(bytecode.clone());
+            }
+        }
+        self.misses += 1;
+        let bytecode = Compiler::compile(source)?;
+        self.entries
+            .insert(key, (source.to_string(), bytecode.clone()));
+        Ok(bytecode)
+    }
+
+    /// Number of cached programs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+fn program(source: &str, options: CompilerOptions) -> Result<CompiledProgram, CompilerError> {
+    let assembly = assemble(source, options)?;
+    if let Some((_, label)) = assembly.unresolved.first() {
+        return Err(CompilerError::InvalidAddress(label.to_string()));
+    }
+    let entry = match assembly.entry {
+        None => 0,
+        Some(target) => match target.parse::<usize>() {
+            Ok(address) => address,
+            Err(_) => *assembly
+                .labels
+                .get(target)
+                .ok_or_else(|| CompilerError::InvalidAddress(target.to_string()))?,
+        },
+    };
+    if entry > assembly.bytecode.len() {
+        return Err(CompilerError::InvalidAddress(entry.to_string()));
+    }
+    let program = CompiledProgram {
+        bytecode: assembly.bytecode,
+        entry,
+    };
+    let mut program = if options.evaluate_constants {
+        evaluate_constants(program)
+    } else {
+        program
+    };
+    if options.relative_jumps {
+        make_jumps_relative(&mut program.bytecode);
+    }
+    Ok(program)
+}
+
+/// Rewrites each absolute jump as an offset from its own address.
+fn make_jumps_relative(bytecode: &mut [OpCode]) {
+    let mut ip = 0;
+    while ip < bytecode.len() {
+        let offset = |target: usize| target as isize - ip as isize;
+        bytecode[ip] = match bytecode[ip] {
+            OpCode::Switch(count) => {
+                ip += 1 + count;
+                continue;
+            }
+            OpCode::Jump(target) => OpCode::JumpRel(offset(target)),
+            OpCode::JumpIfFalse(target) => OpCode::JumpIfFalseRel(offset(target)),
+            OpCode::JumpIfTrue(target) => OpCode::JumpIfTrueRel(offset(target)),
+            opcode => opcode,
+        };
+        ip += 1;
+    }
+}
+
+/// Opcodes whose effect depends only on the stack and that touch neither
+/// the heap nor control flow.
+fn is_pure(opcode: &OpCode) -> bool {
+    match opcode {
+        OpCode::PushConst(value) => !matches!(value, Value::Reference(_)),
+        OpCode::Pop
+        | OpCode::Dup
+        | OpCode::Swap
+        | OpCode::CMov
+        | OpCode::SwapN(..)
+        | OpCode::Add
+        | OpCode::Sub
+        | OpCode::Mul
+        | OpCode::Div
+        | OpCode::SafeDiv
+        | OpCode::Mod
+        | OpCode::Neg
+        | OpCode::Exp
+        | OpCode::Shl
+        | OpCode::Shr
+        | OpCode::ToBool
+        | OpCode::FloatEq => true,
+        _ => false,
+    }
+}
+
+/// Runs `segment` on an empty stack and returns what it leaves behind, or
+/// `None` if it fails, for example by reaching below its own values.
+fn evaluate(segment: &[OpCode]) -> Option<Vec<Value>> {
+    let mut execution = ExecutionContext::new(segment.to_vec());
+    execution.run_sync(&mut Heap::new()).ok()?;
+    // Results that live on the heap, such as big integers, cannot be
+    // turned into constants.
+    if execution
+        .stack
+        .iter()
+        .any(|value| matches!(value, Value::Reference(_)))
+    {
+        return None;
+    }
+    Some(execution.stack)
+}
+
+/// Replaces each foldable segment with pushes of its results followed by
+/// `Nop`s, so that no instruction moves. Addresses are observable through
+/// more than jump operands, for example as integers consumed by `JumpDyn`,
+/// values pushed by `CurrentIp`, or `align` padding, so none may change.
+fn evaluate_constants(mut program: CompiledProgram) -> CompiledProgram {
+    let bytecode = &mut program.bytecode;
+    let mut targets = HashSet::from([program.entry]);
+    for opcode in bytecode.iter() {
+        if let Some(&mut target) = { *opcode }.target_mut() {
+            targets.insert(target);
+        }
+    }
+
+    let mut ip = 0;
+    while ip < bytecode.len() {
+        if !is_pure(&bytecode[ip]) {
+            ip += 1;
+            continue;
+        }
+        // A segment may start at a jump target but never contain one.
+        let start = ip;
+        ip += 1;
+        while ip < bytecode.len() && is_pure(&bytecode[ip]) && !targets.contains(&ip) {
+            ip += 1;
+        }
+        let segment = &mut bytecode[start..ip];
+        if let Some(values) = evaluate(segment).filter(|values| values.len() < segment.len()) {
+            let pushes = values.into_iter().map(OpCode::PushConst);
+            let padding = std::iter::repeat(OpCode::Nop);
+            for (slot, opcode) in segment.iter_mut().zip(pushes.chain(padding)) {
+                *slot = opcode;
+            }
+        }
+    }
+    program
+}
+
+fn lint(program: &CompiledProgram) -> Vec<CompilerWarning> {
+    let bytecode = &program.bytecode;
+    let mut targets = HashSet::from([program.entry]);
+    for opcode in bytecode {
+        if let Some(&mut target) = { *opcode }.target_mut() {
+            targets.insert(target);
+        }
+    }
+    let loaded: HashSet<usize> = bytecode
+        .iter()
+        .filter_map(|opcode| match opcode {
+            OpCode::LoadVar(index) => Some(*index),
+            _ => None,
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    let mut warn = |ip: usize, message: String| warnings.push(CompilerWarning { ip, message });
+    let mut ip = 0;
+    while ip < bytecode.len() {
+        let opcode = bytecode[ip];
+        // A switch table is only reached through its `Switch`, so it is
+        // skipped as a unit rather than read as a run of jumps.
+        let next = match opcode {
+            OpCode::Switch(count) => ip + 1 + count,
+            _ => ip + 1,
+        };
+        match opcode {
+            OpCode::Jump(target) if target == ip => {
+                warn(ip, "Jump to itself loops forever".to_string());
+            }
+            OpCode::StoreVar(index) | OpCode::Tee(index) if !loaded.contains(&index) => {
+                warn(ip, format!("Variable {} is stored but never loaded", index));
+            }
+            _ => {}
+        }
+        let terminates = matches!(
+            opcode,
+            OpCode::Return | OpCode::Jump(_) | OpCode::TailCall(_) | OpCode::Halt
+        );
+        if terminates && next < bytecode.len() && !targets.contains(&next) {
+            warn(next, format!("Unreachable code after {:?}", opcode));
+        }
+        ip = next;
+    }
+    warnings
+}
+
+fn check_variables(bytecode: &[OpCode]) -> Result<(), CompilerError> {
+    let mut stored = HashSet::new();
+    for opcode in bytecode {
+        match opcode {
+            OpCode::StoreVar(index) | OpCode::Tee(index) => {
+                stored.insert(*index);
+            }
+            OpCode::LoadVar(index) | OpCode::UpdateVar(index) if !stored.contains(index) => {
+                return Err(CompilerError::UndefinedVariable(*index));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Bytecode together with its label table, the label references that could
+/// not be resolved within the source, and the unresolved `entry` target.
+#[derive(Default)]
+struct Assembly<'a> {
+    bytecode: Vec<OpCode>,
+    labels: HashMap<&'a str, usize>,
+    unresolved: Vec<(usize, &'a str)>,
+    entry: Option<&'a str>,
+}
+
+fn assemble(source: &str, options: CompilerOptions) -> Result<Assembly<'_>, CompilerError> {
+    let mut assembly = Assembly::default();
+    let mut fixups = Vec::new();
+    for item in Parser::new(source, options) {
+        match item? {
+            Item::Op(opcode) => assembly.bytecode.push(opcode),
+            Item::Fixup(opcode, label) => {
+                fixups.push((assembly.bytecode.len(), label));
+                assembly.bytecode.push(opcode);
+            }
+            Item::Label(name) => define_label(&mut assembly.labels, name, assembly.bytecode.len())?,
+            Item::Entry(target) => {
+                if assembly.entry.replace(target).is_some() {
+                    return Err(CompilerError::ParseError(
+                        "Duplicate entry directive".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+    for (ip, label) in fixups {
+        match (
+            assembly.labels.get(label),
+            assembly.bytecode[ip].target_mut(),
+        ) {
+            (Some(&address), Some(target)) => *target = address,
+            _ => assembly.unresolved.push((ip, label)),
+        }
+    }
+    Ok(assembly)
+}
+
+fn define_label<'a>(
+    labels: &mut HashMap<&'a str, usize>,
+    name: &'a str,
+    address: usize,
+) -> Result<(), CompilerError> {
+    if labels.insert(name, address).is_some() {
+        return Err(CompilerError::DuplicateLabel(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Characters that form a token on their own even without surrounding
+/// whitespace, so `[1,2]` splits into `[`, `1`, `,`, `2`, `]`.
+fn is_delimiter(c: char) -> bool {
+    matches!(c, '[' | ']' | ',')
+}
+
+/// Splits source on whitespace and delimiters, keeping a double-quoted string
+/// together as a single token even when it contains spaces. Blank lines,
+/// tabs, and `\r\n` line endings are all just whitespace, so how a program
+/// is laid out never changes its bytecode.
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let source = self.rest.trim_start();
+        if source.is_empty() {
+            self.rest = source;
+            return None;
+        }
+        let end = match source.strip_prefix('"') {
+            Some(body) => body.find('"').map_or(source.len(), |i| i + 2),
+            None if source.starts_with(is_delimiter) => 1,
+            None => source
+                .find(|c: char| c.is_whitespace() || is_delimiter(c))
+                .unwrap_or(source.len()),
+        };
+        let (token, rest) = source.split_at(end);
+        self.rest = rest;
+        Some(token)
+    }
+}
+
+/// A unit of parsed source, before label resolution.
+enum Item<'a> {
+    Op(OpCode),
+    /// An opcode whose address operand names a label.
+    Fixup(OpCode, &'a str),
+    /// A `name:` definition marking the address of the next opcode.
+    Label(&'a str),
+    /// An `entry` directive naming a bytecode index or a label.
+    Entry(&'a str),
+}
+
+struct Parser<'a> {
+    tokens: Peekable<Tokens<'a>>,
+    /// Items already parsed but not yet handed out, for tokens that expand
+    /// to more than one item.
+    pending: VecDeque<Item<'a>>,
+    /// Address of the next opcode handed out.
+    position: usize,
+    options: CompilerOptions,
+    /// Loops being parsed, innermost last.
+    loops: Vec<Loop>,
+}
+
+/// A loop whose body is being parsed: its start address and the pending
+/// indices of the jumps that leave it.
+struct Loop {
+    start: usize,
+    exits: Vec<usize>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, options: CompilerOptions) -> Self {
+        Parser {
+            tokens: Tokens { rest: source }.peekable(),
+            pending: VecDeque::new(),
+            position: 0,
+            options,
+            loops: Vec::new(),
+        }
+    }
+
+    fn parse(&mut self, token: &'a str) -> Result<(), CompilerError> {
+        match token {
+            // `Switch N a0 .. aN-1` expands to the switch followed by its
+            // inline table of `N` jumps.
+            "Switch" => {
+                let count = operand(&mut self.tokens, "table size", token)?;
+                self.pending.push_back(Item::Op(OpCode::Switch(count)));
+                for _ in 0..count {
+                    let entry = address(&mut self.tokens, token, OpCode::Jump)?;
+                    self.pending.push_back(entry);
+                }
+            }
+            // `align N` pads with `Nop`s until the next opcode's address is a
+            // multiple of `N`.
+            "align" => {
+                let alignment = operand(&mut self.tokens, "alignment", token)?;
+                if alignment == 0 {
+                    return Err(CompilerError::ParseError(
+                        "align requires a non-zero alignment".to_string(),
+                    ));
+                }
+                let padding = (alignment - self.next_address() % alignment) % alignment;
+                self.pending
+                    .extend((0..padding).map(|_| Item::Op(OpCode::Nop)));
+            }
+            "entry" => {
+                let target = self.tokens.next().ok_or_else(|| {
+                    CompilerError::InvalidAddress("expected address after entry".to_string())
+                })?;
+                if target.parse::<usize>().is_err() && !is_label(target) {
+                    return Err(CompilerError::InvalidAddress(target.to_string()));
+                }
+                self.pending.push_back(Item::Entry(target));
+            }
+            "[" => self.parse_array()?,
+            "loop" | "while" => self.parse_loop(token)?,
+            "if" => self.parse_if()?,
+            "fn" => self.parse_fn()?,
+            "break" => {
+                let index = self.pending.len();
+                let open = self.loops.last_mut().ok_or_else(|| {
+                    CompilerError::ParseError("break outside of a loop".to_string())
+                })?;
+                open.exits.push(index);
+                self.pending.push_back(Item::Op(OpCode::Jump(0)));
+            }
+            "continue" => {
+                let open = self.loops.last().ok_or_else(|| {
+                    CompilerError::ParseError("continue outside of a loop".to_string())
+                })?;
+                self.pending.push_back(Item::Op(OpCode::Jump(open.start)));
+            }
+            "do" | "else" | "end" => {
+                return Err(CompilerError::ParseError(format!(
+                    "{} outside of a block",
+                    token
+                )));
+            }
+            _ => {
+                let item = match parse_token(token, &mut self.tokens) {
+                    Err(CompilerError::InvalidToken(_)) if self.options.bare_word_strings => {
+                        Item::Op(OpCode::PushString(Symbol::intern(token)))
                     }
-                    "SpawnSupervisor" => {
-                        let addr_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress(
-                                "expected address after SpawnSupervisor".into(),
-                            )
-                        })?;
-                        let addr = addr_token
-                            .parse::<usize>()
-                            .map_err(|_| CompilerError::InvalidAddress(addr_token.to_string()))?;
-                        bytecode.push(OpCode::SpawnSupervisor(addr));
+                    result => result?,
+                };
+                self.pending.push_back(item);
+            }
+        }
+        Ok(())
+    }
+
+    /// Address of the next opcode to be parsed, counting those still pending.
+    fn next_address(&self) -> usize {
+        let pending = self.pending.iter();
+        self.position
+            + pending
+                .filter(|item| matches!(item, Item::Op(_) | Item::Fixup(..)))
+                .count()
+    }
+
+    /// Expands `loop BODY end` or `while COND do BODY end` into jumps.
+    ///
+    /// The whole loop is parsed before any of it is handed out, so `break`
+    /// jumps can be patched with the address after the loop. `continue`
+    /// jumps back to the start, which for `while` re-evaluates the condition.
+    fn parse_loop(&mut self, keyword: &str) -> Result<(), CompilerError> {
+        let start = self.next_address();
+        let mut exits = Vec::new();
+        if keyword == "while" {
+            self.parse_block(keyword, &["do"])?;
+            exits.push(self.pending.len());
+            self.pending.push_back(Item::Op(OpCode::JumpIfFalse(0)));
+        }
+        self.loops.push(Loop { start, exits });
+        let body = self.parse_block(keyword, &["end"]);
+        let exits = self.loops.pop().map(|open| open.exits).unwrap_or_default();
+        body?;
+        self.pending.push_back(Item::Op(OpCode::Jump(start)));
+        let end = self.next_address();
+        for index in exits {
+            self.patch(index, end);
+        }
+        Ok(())
+    }
+
+    /// Expands `if THEN end` or `if THEN else ELSE end`, which branch on the
+    /// boolean on top of the stack.
+    fn parse_if(&mut self) -> Result<(), CompilerError> {
+        let branch = self.pending.len();
+        self.pending.push_back(Item::Op(OpCode::JumpIfFalse(0)));
+        if self.parse_block("if", &["else", "end"])? == "else" {
+            let skip = self.pending.len();
+            self.pending.push_back(Item::Op(OpCode::Jump(0)));
+            self.patch(branch, self.next_address());
+            self.parse_block("if", &["end"])?;
+            self.patch(skip, self.next_address());
+        } else {
+            self.patch(branch, self.next_address());
+        }
+        Ok(())
+    }
+
+    /// Expands `fn name BODY end` into a subroutine labelled `name` that
+    /// returns when its body runs out. Code around the definition jumps over
+    /// it, so functions can be defined anywhere.
+    fn parse_fn(&mut self) -> Result<(), CompilerError> {
+        let name = self
+            .tokens
+            .next()
+            .filter(|name| is_label(name))
+            .ok_or_else(|| CompilerError::ParseError("expected a name after fn".to_string()))?;
+        let skip = self.pending.len();
+        self.pending.push_back(Item::Op(OpCode::Jump(0)));
+        self.pending.push_back(Item::Label(name));
+        // `break` and `continue` cannot reach loops outside the function.
+        let loops = std::mem::take(&mut self.loops);
+        let body = self.parse_block("fn", &["end"]);
+        self.loops = loops;
+        body?;
+        self.pending.push_back(Item::Op(OpCode::Return));
+        self.patch(skip, self.next_address());
+        Ok(())
+    }
+
+    /// Parses tokens until one of `terminators`, which is returned.
+    fn parse_block(
+        &mut self,
+        keyword: &str,
+        terminators: &[&str],
+    ) -> Result<&'a str, CompilerError> {
+        loop {
+            let token = self
+                .tokens
+                .next()
+                .ok_or_else(|| CompilerError::ParseError(format!("Unterminated {}", keyword)))?;
+            if terminators.contains(&token) {
+                return Ok(token);
+            }
+            self.parse(token)?;
+        }
+    }
+
+    /// Points the pending jump at `index` to `address`.
+    fn patch(&mut self, index: usize, address: usize) {
+        if let Some(target) = self.pending.get_mut(index).and_then(|item| match item {
+            Item::Op(opcode) => opcode.target_mut(),
+            _ => None,
+        }) {
+            *target = address;
+        }
+    }
+
+    /// Expands an array literal such as `[1, 2.5, [true]]` into code that
+    /// builds the array at runtime, so every execution yields a fresh array.
+    fn parse_array(&mut self) -> Result<(), CompilerError> {
+        self.pending.push_back(Item::Op(OpCode::NewArray));
+        loop {
+            let token = self.tokens.next().ok_or_else(|| {
+                CompilerError::ParseError("Unterminated array literal".to_string())
+            })?;
+            match token {
+                "]" => return Ok(()),
+                "," => continue,
+                "[" => self.parse_array()?,
+                _ => match parse_token(token, &mut self.tokens)? {
+                    item @ Item::Op(OpCode::PushConst(_)) => self.pending.push_back(item),
+                    _ => {
+                        return Err(CompilerError::ParseError(format!(
+                            "Invalid array element: {}",
+                            token
+                        )))
                     }
-                    "SetStrategy" => {
-                        let strategy_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress(
-                                "expected strategy after SetStrategy".into(),
-                            )
-                        })?;
-                        let strategy = strategy_token.parse::<usize>().map_err(|_| {
-                            CompilerError::InvalidAddress(strategy_token.to_string())
-                        })?;
-                        bytecode.push(OpCode::SetStrategy(strategy));
+                },
+            }
+            self.pending.push_back(Item::Op(OpCode::ArrayPush));
+        }
+    }
+}
+
+impl<'a> Iterator for Parser<'a> {
+    type Item = Result<Item<'a>, CompilerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                if matches!(item, Item::Op(_) | Item::Fixup(..)) {
+                    self.position += 1;
+                }
+                return Some(Ok(item));
+            }
+            let token = self.tokens.next()?;
+            if let Err(err) = self.parse(token) {
+                self.pending.clear();
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+struct OpCodeStream<'a> {
+    items: Parser<'a>,
+    labels: HashMap<&'a str, usize>,
+    position: usize,
+    failed: bool,
+}
+
+impl OpCodeStream<'_> {
+    fn resolve(&mut self) -> Option<Result<OpCode, CompilerError>> {
+        loop {
+            let opcode = match self.items.next()? {
+                Ok(Item::Op(opcode)) => opcode,
+                Ok(Item::Fixup(mut opcode, label)) => {
+                    match (self.labels.get(label), opcode.target_mut()) {
+                        (Some(&address), Some(target)) => *target = address,
+                        _ => return Some(Err(CompilerError::InvalidAddress(label.to_string()))),
                     }
-                    "RestartChild" => {
-                        let child_token = tokens.next().ok_or_else(|| {
-                            CompilerError::InvalidAddress(
-                                "expected child index after RestartChild".into(),
-                            )
-                        })?;
-                        let child = child_token
-                            .parse::<usize>()
-                            .map_err(|_| CompilerError::InvalidAddress(child_token.to_string()))?;
-                        bytecode.push(OpCode::RestartChild(child));
+                    opcode
+                }
+                Ok(Item::Label(name)) => {
+                    if let Err(err) = define_label(&mut self.labels, name, self.position) {
+                        return Some(Err(err));
                     }
-                    "Return" => bytecode.push(OpCode::Return),
-                    _ => return Err(CompilerError::InvalidToken(token.to_string())),
+                    continue;
                 }
-            }
+                Ok(Item::Entry(_)) => continue,
+                Err(err) => return Some(Err(err)),
+            };
+            self.position += 1;
+            return Some(Ok(opcode));
         }
+    }
+}
 
-        Ok(bytecode)
+impl Iterator for OpCodeStream<'_> {
+    type Item = Result<OpCode, CompilerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.resolve()?;
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// An integer literal too large for `i32`, such as `99999999999`.
+fn is_big_integer(token: &str) -> bool {
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Reads the numeric operand that follows `opcode`, e.g. the index in
+/// `StoreVar 0`.
+fn operand<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    expected: &str,
+    opcode: &str,
+) -> Result<usize, CompilerError> {
+    let token = tokens.next().ok_or_else(|| {
+        CompilerError::InvalidAddress(format!("expected {} after {}", expected, opcode))
+    })?;
+    token
+        .parse::<usize>()
+        .map_err(|_| CompilerError::InvalidAddress(token.to_string()))
+}
+
+/// Reads the address operand that follows `opcode`, which is either a numeric
+/// bytecode index or the name of a label.
+fn address<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    opcode: &str,
+    build: fn(usize) -> OpCode,
+) -> Result<Item<'a>, CompilerError> {
+    let token = tokens.next().ok_or_else(|| {
+        CompilerError::InvalidAddress(format!("expected address after {}", opcode))
+    })?;
+    match token.parse::<usize>() {
+        Ok(address) => Ok(Item::Op(build(address))),
+        Err(_) if is_label(token) => Ok(Item::Fixup(build(0), token)),
+        Err(_) => Err(CompilerError::InvalidAddress(token.to_string())),
     }
 }
+
+/// Strips the quotes from a string token produced by [`Tokens`].
+fn string_literal(token: &str) -> Result<&str, CompilerError> {
+    token
+        .strip_prefix('"')
+        .and_then(|body| body.strip_suffix('"'))
+        .ok_or_else(|| CompilerError::ParseError(format!("Unterminated string: {}", token)))
+}
+
+fn parse_token<'a>(
+    token: &'a str,
+    tokens: &mut Peekable<Tokens<'a>>,
+) -> Result<Item<'a>, CompilerError> {
+    if let Some(name) = token.strip_suffix(':').filter(|name| is_label(name)) {
+        return Ok(Item::Label(name));
+    }
+    if token == "true" || token == "false" {
+        return Ok(Item::Op(OpCode::PushConst(Value::Boolean(token == "true"))));
+    }
+    if token.contains('.') {
+        let num = token
+            .parse::<f64>()
+            .map_err(|_| CompilerError::ParseError(format!("Invalid float: {}", token)))?;
+        return Ok(Item::Op(OpCode::PushConst(Value::Float(num))));
+    }
+    if let Ok(num) = token.parse::<i32>() {
+        return Ok(Item::Op(OpCode::PushConst(Value::Integer(num))));
+    }
+    if is_big_integer(token) {
+        return Ok(Item::Op(OpCode::PushBigInt(Symbol::intern(token))));
+    }
+    if token.starts_with('"') {
+        let text = string_literal(token)?;
+        return Ok(Item::Op(OpCode::PushString(Symbol::intern(text))));
+    }
+
+    let opcode = match token {
+        "StoreVar" => OpCode::StoreVar(operand(tokens, "variable index", token)?),
+        "LoadVar" => OpCode::LoadVar(operand(tokens, "variable index", token)?),
+        "Tee" => OpCode::Tee(operand(tokens, "variable index", token)?),
+        "ClearVar" => OpCode::ClearVar(operand(tokens, "variable index", token)?),
+        "UpdateVar" => OpCode::UpdateVar(operand(tokens, "variable index", token)?),
+        "Nop" => OpCode::Nop,
+        "Pop" => OpCode::Pop,
+        "Dup" => OpCode::Dup,
+        "Swap" => OpCode::Swap,
+        "CMov" => OpCode::CMov,
+        "SwapN" => OpCode::SwapN(
+            operand(tokens, "stack depth", token)?,
+            operand(tokens, "stack depth", token)?,
+        ),
+        "+" | "Add" => OpCode::Add,
+        "-" | "Sub" => OpCode::Sub,
+        "*" | "Mul" => OpCode::Mul,
+        "/" | "Div" => OpCode::Div,
+        "SafeDiv" => OpCode::SafeDiv,
+        "%" | "Mod" => OpCode::Mod,
+        "Neg" => OpCode::Neg,
+        "Exp" | "^" => OpCode::Exp,
+        "Shl" | "<<" => OpCode::Shl,
+        "Shr" | ">>" => OpCode::Shr,
+        "ToBool" => OpCode::ToBool,
+        "FloatEq" => OpCode::FloatEq,
+        "Jump" => return address(tokens, token, OpCode::Jump),
+        "JumpIfFalse" => return address(tokens, token, OpCode::JumpIfFalse),
+        "JumpIfTrue" => return address(tokens, token, OpCode::JumpIfTrue),
+        "JumpDyn" => OpCode::JumpDyn,
+        "Call" => return address(tokens, token, OpCode::Call),
+        "TailCall" => return address(tokens, token, OpCode::TailCall),
+        "Return" => OpCode::Return,
+        "CallDepth" => OpCode::CallDepth,
+        "Halt" => OpCode::Halt,
+        "Yield" => OpCode::Yield,
+        "SpawnActor" => return address(tokens, token, OpCode::SpawnActor),
+        "Fork" => return address(tokens, token, OpCode::Fork),
+        "SpawnDetached" => return address(tokens, token, OpCode::SpawnDetached),
+        "SendMessage" => OpCode::SendMessage,
+        "ReceiveMessage" => OpCode::ReceiveMessage,
+        "DrainMailbox" => OpCode::DrainMailbox,
+        "SendAll" => OpCode::SendAll,
+        "SendIfAlive" => OpCode::SendIfAlive,
+        "NewChannel" => OpCode::NewChannel,
+        "ChannelSend" => OpCode::ChannelSend,
+        "ChannelRecv" => OpCode::ChannelRecv,
+        "Select" => OpCode::Select,
+        "JoinActor" => OpCode::JoinActor,
+        "SpawnSupervisor" => return address(tokens, token, OpCode::SpawnSupervisor),
+        "SetStrategyDyn" => OpCode::SetStrategyDyn,
+        "GetStrategy" => OpCode::GetStrategy,
+        "SetStrategy" => OpCode::SetStrategy(operand(tokens, "strategy", token)?),
+        "RestartChild" => OpCode::RestartChild(operand(tokens, "child index", token)?),
+        "NewBytes" => OpCode::NewBytes,
+        "BytesGet" => OpCode::BytesGet,
+        "BytesLen" => OpCode::BytesLen,
+        "BytesSet" => OpCode::BytesSet,
+        "Serialize" => OpCode::Serialize,
+        "Deserialize" => OpCode::Deserialize,
+        "NewSet" => OpCode::NewSet,
+        "SetAdd" => OpCode::SetAdd,
+        "SetHas" => OpCode::SetHas,
+        "SetUnion" => OpCode::SetUnion,
+        "SetIntersect" => OpCode::SetIntersect,
+        "NewCounter" => OpCode::NewCounter,
+        "CounterInc" => OpCode::CounterInc,
+        "CounterGet" => OpCode::CounterGet,
+        "NewArray" => OpCode::NewArray,
+        "ArrayPush" => OpCode::ArrayPush,
+        "ArraySlice" => OpCode::ArraySlice,
+        "Collect" => OpCode::Collect(operand(tokens, "value count", token)?),
+        "Spread" => OpCode::Spread,
+        "StrCodePoints" => OpCode::StrCodePoints,
+        "ArrayToStr" => OpCode::ArrayToStr,
+        "Unbox" => OpCode::Unbox,
+        "ArrayMap" => return address(tokens, token, OpCode::ArrayMap),
+        "ArrayFilter" => return address(tokens, token, OpCode::ArrayFilter),
+        "ArrayReduce" => return address(tokens, token, OpCode::ArrayReduce),
+        "Rand" => OpCode::Rand,
+        "RandRange" => OpCode::RandRange,
+        "Now" => OpCode::Now,
+        "HeapSize" => OpCode::HeapSize,
+        "IsValid" => OpCode::IsValid,
+        "RefCount" => OpCode::RefCount,
+        "RefEq" => OpCode::RefEq,
+        "ActorIp" => OpCode::ActorIp,
+        "CurrentIp" => OpCode::CurrentIp,
+        "Version" => OpCode::Version,
+        "Print" => OpCode::Print,
+        "ExpectDepth" => OpCode::ExpectDepth(operand(tokens, "stack depth", token)?),
+        "Assert" => {
+            let message = match tokens.next_if(|next| next.starts_with('"')) {
+                Some(message) => Some(Symbol::intern(string_literal(message)?)),
+                None => None,
+            };
+            OpCode::Assert(message)
+        }
+        _ => return Err(CompilerError::InvalidToken(token.to_string())),
+    };
+    Ok(Item::Op(opcode))
+}
@@ -1,21 +1,25 @@
 // src/lib.rs
 
 pub mod compiler;
+pub mod linker;
 pub mod runtime;
 pub mod vm;
 
-pub use compiler::{Compiler, CompilerError};
-pub use runtime::Actor;
-pub use vm::VM;
+pub use compiler::{
+    CompileCache, CompiledProgram, Compiler, CompilerError, CompilerOptions, CompilerWarning,
+};
+pub use linker::{LinkError, Linker};
+pub use runtime::{Actor, OnError};
+pub use vm::{opcodes, RunResult, StepAction, VmBuilder, VM};
 
 use crate::vm::VmError;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Runs a Raft program from source code
-pub async fn run(source: &str) -> Result<(), VmError> {
-    let bytecode = Compiler::compile(source)?;
+pub async fn run(source: &str) -> Result<RunResult, VmError> {
+    let program = Compiler::compile_program(source)?;
 
-    let (mut vm, _tx) = VM::new(bytecode, None);
+    let (mut vm, _tx) = VM::from_program(program, None);
     vm.run().await
 }
@@ -0,0 +1,96 @@
+// src/linker.rs
+
+use std::collections::HashMap;
+
+use crate::vm::opcodes::OpCode;
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone)]
+pub enum LinkError {
+    #[error("Unresolved symbol {symbol} in module {module}")]
+    UnresolvedSymbol { module: String, symbol: String },
+    #[error("Duplicate symbol {symbol} in module {module}")]
+    DuplicateSymbol { module: String, symbol: String },
+}
+
+/// A separately compiled unit of bytecode, usually produced by
+/// [`Compiler::compile_module`](crate::compiler::Compiler::compile_module).
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub bytecode: Vec<OpCode>,
+    /// Labels other modules may reference, as module-relative addresses.
+    pub exports: HashMap<String, usize>,
+    /// Instructions whose address operand names a label defined elsewhere.
+    pub imports: Vec<(usize, String)>,
+}
+
+/// Concatenates modules into a single program.
+///
+/// Modules are laid out in the order they were added, so execution starts at
+/// the first instruction of the first module. Address operands are offset by
+/// each module's position and imports are patched with the final address of
+/// the exporting label.
+#[derive(Debug, Default)]
+pub struct Linker {
+    modules: Vec<Module>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, module: Module) -> &mut Self {
+        self.modules.push(module);
+        self
+    }
+
+    pub fn link(&self) -> Result<Vec<OpCode>, LinkError> {
+        let mut symbols = HashMap::new();
+        let mut bases = Vec::with_capacity(self.modules.len());
+        let mut base = 0;
+        for module in &self.modules {
+            for (symbol, address) in &module.exports {
+                if symbols.insert(symbol.as_str(), base + address).is_some() {
+                    return Err(LinkError::DuplicateSymbol {
+                        module: module.name.clone(),
+                        symbol: symbol.clone(),
+                    });
+                }
+            }
+            bases.push(base);
+            base += module.bytecode.len();
+        }
+
+        let mut bytecode = Vec::with_capacity(base);
+        for (module, base) in self.modules.iter().zip(bases) {
+            bytecode.extend(module.bytecode.iter().map(|opcode| {
+                let mut opcode = *opcode;
+                if let Some(target) = opcode.target_mut() {
+                    *target += base;
+                }
+                opcode
+            }));
+            for (ip, symbol) in &module.imports {
+                let address =
+                    *symbols
+                        .get(symbol.as_str())
+                        .ok_or_else(|| LinkError::UnresolvedSymbol {
+                            module: module.name.clone(),
+                            symbol: symbol.clone(),
+                        })?;
+                if let Some(target) = bytecode[base + ip].target_mut() {
+                    *target = address;
+                }
+            }
+            log::info!(
+                "Linked module {} at offset {} ({} opcodes)",
+                module.name,
+                base,
+                module.bytecode.len()
+            );
+        }
+        Ok(bytecode)
+    }
+}
@@ -20,7 +20,6 @@ use tokio::io::{self, AsyncBufReadExt};
 
 #[derive(Parser)]
 #[command(name = "raft",author, version, about, long_about = None)]
-
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
@@ -60,7 +59,7 @@ fn print_version() {
 async fn handle_run(filename: &str) {
     match fs::read_to_string(filename) {
         Ok(source) => {
-            let bytecode = match Compiler::compile(&source) {
+            let program = match Compiler::compile_program(&source) {
                 Ok(b) => b,
                 Err(e) => {
                     let err: VmError = e.into();
@@ -68,7 +67,7 @@ async fn handle_run(filename: &str) {
                     process::exit(1);
                 }
             };
-            let (mut vm, tx) = VM::new(bytecode, None);
+            let (mut vm, tx) = VM::from_program(program, None);
 
             // Simulate sending messages to the VM
             tokio::spawn(async move {
@@ -111,6 +110,10 @@ async fn start_repl() {
         if input.trim() == "exit" {
             break;
         }
+        if let Some(topic) = input.trim().strip_prefix(":help") {
+            help(topic.trim());
+            continue;
+        }
         match run(&input).await {
             Ok(_) => println!("Success"),
             Err(e) => eprintln!("Error: {}", e),
@@ -118,4 +121,19 @@ async fn start_repl() {
     }
 }
 
+/// Prints the description of the opcode named `topic`, or of every opcode
+/// when `topic` is empty.
+fn help(topic: &str) {
+    if topic.is_empty() {
+        for (name, description) in raft::opcodes::all_descriptions() {
+            println!("{name:<16} {description}");
+        }
+        return;
+    }
+    match raft::opcodes::description(topic) {
+        Some(description) => println!("{topic}: {description}"),
+        None => eprintln!("Unknown opcode: {topic}"),
+    }
+}
+
 // Removed unused utility functions that produced dead-code warnings.
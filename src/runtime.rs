@@ -4,20 +4,44 @@ use tokio::sync::mpsc::Sender;
 
 use crate::vm::error::VmError;
 use crate::vm::value::Value;
-use crate::vm::{OpCode, VM};
+use crate::vm::{OpCode, RunResult, VM};
+
+/// How many times [`Actor::run_supervised`] restarts a failing actor under
+/// [`OnError::Restart`] before giving up.
+pub const DEFAULT_MAX_RESTARTS: usize = 3;
+
+/// What an [`Actor`] does when its VM fails with a recoverable error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnError {
+    /// Propagate the error and stop running.
+    #[default]
+    Stop,
+    /// Reset the VM and run it again from its entry point. Messages still
+    /// queued in the mailbox are processed by the restarted actor.
+    Restart,
+    /// Log the error and carry on with the instruction after the fault.
+    Ignore,
+}
 
 /// A lightweight wrapper around a `VM` that exposes a mailbox
 /// for message passing.
 pub struct Actor {
     vm: VM,
     sender: Sender<Value>,
+    policy: OnError,
+    max_restarts: usize,
 }
 
 impl Actor {
     /// Create a new actor from bytecode.
     pub fn new(bytecode: Vec<OpCode>) -> Self {
         let (vm, tx) = VM::new(bytecode, None);
-        Actor { vm, sender: tx }
+        Actor {
+            vm,
+            sender: tx,
+            policy: OnError::default(),
+            max_restarts: DEFAULT_MAX_RESTARTS,
+        }
     }
 
     /// Obtain a sender that can be used to send messages to this actor.
@@ -25,22 +49,117 @@ impl Actor {
         self.sender.clone()
     }
 
+    /// The actor's VM, for inspecting its state.
+    pub fn vm(&self) -> &VM {
+        &self.vm
+    }
+
+    pub fn set_policy(&mut self, policy: OnError) {
+        self.policy = policy;
+    }
+
+    pub fn set_max_restarts(&mut self, max_restarts: usize) {
+        self.max_restarts = max_restarts;
+    }
+
     /// Send a message to the actor's mailbox.
     pub async fn send(&self, msg: Value) -> Result<(), VmError> {
-        self.sender.send(msg).await.map_err(|e| {
-            let error = e.to_string();
-            let value = e.0;
-            VmError::ChannelSend { error, value }
-        })
+        Ok(self.sender.send(msg).await?)
     }
 
-    /// Execute the actor until its VM halts.
-    pub async fn run(&mut self) -> Result<(), VmError> {
+    /// Execute the actor until its VM completes, halts, or yields.
+    pub async fn run(&mut self) -> Result<RunResult, VmError> {
         self.vm.run().await
     }
 
+    /// Process every queued message, then return.
+    ///
+    /// Each message is handled by one pass of the bytecode from its entry
+    /// point, which is expected to take the message with `ReceiveMessage`.
+    /// The stack and locals carry over between passes, so an actor can
+    /// accumulate state across messages. Returns [`RunResult::Completed`] as
+    /// soon as the mailbox is empty, without waiting for further messages.
+    ///
+    /// A pass that yields, pauses, or hits a breakpoint returns its result
+    /// straight away; [`Actor::run`] continues it. A pass that ends without
+    /// taking a message fails with [`VmError::MessageNotConsumed`] rather
+    /// than running again forever.
+    pub async fn run_to_idle(&mut self) -> Result<RunResult, VmError> {
+        while !self.vm.mailbox.is_empty() {
+            let received = self.vm.messages_received();
+            self.vm.set_ip(self.vm.entry());
+            match self.vm.run().await? {
+                RunResult::Completed | RunResult::Halted => {}
+                interrupted => return Ok(interrupted),
+            }
+            if self.vm.messages_received() == received {
+                let queued = self.vm.mailbox.len();
+                log::error!(
+                    "[vm {}] Pass took none of {} queued messages",
+                    self.vm.id(),
+                    queued
+                );
+                return Err(VmError::MessageNotConsumed(queued));
+            }
+        }
+        Ok(RunResult::Completed)
+    }
+
+    /// Execute the actor, applying its [`OnError`] policy to recoverable
+    /// errors. A closed mailbox, cancellation, or missing bytecode always
+    /// stops the actor.
+    pub async fn run_supervised(&mut self) -> Result<RunResult, VmError> {
+        let mut restarts = 0;
+        loop {
+            let error = match self.vm.run().await {
+                Ok(result) => return Ok(result),
+                Err(error) => error,
+            };
+            if is_fatal(&error) {
+                return Err(error);
+            }
+            match self.policy {
+                OnError::Stop => return Err(error),
+                OnError::Restart if restarts < self.max_restarts => {
+                    restarts += 1;
+                    log::warn!(
+                        "[vm {}] Restarting actor after error ({}/{}): {}",
+                        self.vm.id(),
+                        restarts,
+                        self.max_restarts,
+                        error
+                    );
+                    self.vm.reset();
+                }
+                OnError::Restart => {
+                    log::error!(
+                        "[vm {}] Actor exceeded {} restarts",
+                        self.vm.id(),
+                        self.max_restarts
+                    );
+                    return Err(error);
+                }
+                OnError::Ignore => {
+                    log::warn!(
+                        "[vm {}] Ignoring actor error at ip {}: {}",
+                        self.vm.id(),
+                        self.vm.ip(),
+                        error
+                    );
+                }
+            }
+        }
+    }
+
     /// Receive the next message if available.
     pub async fn handle_next_message(&mut self) -> Option<Value> {
         self.vm.mailbox.recv().await
     }
 }
+
+fn is_fatal(error: &VmError) -> bool {
+    matches!(
+        error,
+        VmError::NoBytecode | VmError::MailboxClosed | VmError::Cancelled
+    )
+}
@@ -0,0 +1,127 @@
+// src/vm/builder.rs
+
+use crate::vm::execution::Clock;
+use crate::vm::value::Value;
+use crate::vm::vm::DEFAULT_MAILBOX_CAPACITY;
+use crate::vm::{OpCode, VM};
+use tokio::sync::mpsc::Sender;
+
+/// Collects the options a VM can be created with, for hosts that set more
+/// than a couple of them. [`VM::new`] is the same as building with no
+/// options other than the supervisor.
+#[derive(Debug)]
+pub struct VmBuilder {
+    supervisor: Option<Sender<usize>>,
+    mailbox_capacity: usize,
+    stack_capacity: Option<usize>,
+    heap_capacity: Option<usize>,
+    max_actors: Option<usize>,
+    default_locals_to_null: bool,
+    seed: Option<u64>,
+    clock: Option<Clock>,
+    entry: usize,
+}
+
+impl Default for VmBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VmBuilder {
+    pub fn new() -> Self {
+        Self {
+            supervisor: None,
+            mailbox_capacity: DEFAULT_MAILBOX_CAPACITY,
+            stack_capacity: None,
+            heap_capacity: None,
+            max_actors: None,
+            default_locals_to_null: false,
+            seed: None,
+            clock: None,
+            entry: 0,
+        }
+    }
+
+    pub fn supervisor(mut self, supervisor: Sender<usize>) -> Self {
+        self.supervisor = Some(supervisor);
+        self
+    }
+
+    /// Number of messages the mailbox queues before senders wait.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn mailbox_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "mailbox capacity must be greater than zero");
+        self.mailbox_capacity = capacity;
+        self
+    }
+
+    /// See [`VM::with_stack_capacity`].
+    pub fn stack_capacity(mut self, capacity: usize) -> Self {
+        self.stack_capacity = Some(capacity);
+        self
+    }
+
+    /// See [`VM::with_heap_capacity`].
+    pub fn heap_capacity(mut self, capacity: usize) -> Self {
+        self.heap_capacity = Some(capacity);
+        self
+    }
+
+    /// See [`VM::with_max_actors`].
+    pub fn max_actors(mut self, max_actors: usize) -> Self {
+        self.max_actors = Some(max_actors);
+        self
+    }
+
+    /// See [`VM::with_default_locals_to_null`].
+    pub fn default_locals_to_null(mut self, enabled: bool) -> Self {
+        self.default_locals_to_null = enabled;
+        self
+    }
+
+    /// See [`VM::set_seed`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// See [`VM::set_clock`].
+    pub fn clock(mut self, clock: impl Fn() -> u64 + Send + 'static) -> Self {
+        self.clock = Some(Clock(Box::new(clock)));
+        self
+    }
+
+    /// See [`VM::set_entry`].
+    pub fn entry(mut self, entry: usize) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    /// Creates the VM for `bytecode`, returning it with the sender for its
+    /// mailbox.
+    pub fn build(self, bytecode: Vec<OpCode>) -> (VM, Sender<Value>) {
+        let (mut vm, tx) = VM::with_mailbox(bytecode, self.supervisor, self.mailbox_capacity);
+        if let Some(capacity) = self.stack_capacity {
+            vm = vm.with_stack_capacity(capacity);
+        }
+        if let Some(capacity) = self.heap_capacity {
+            vm = vm.with_heap_capacity(capacity);
+        }
+        if let Some(max_actors) = self.max_actors {
+            vm = vm.with_max_actors(max_actors);
+        }
+        vm = vm.with_default_locals_to_null(self.default_locals_to_null);
+        if let Some(seed) = self.seed {
+            vm.set_seed(seed);
+        }
+        if let Some(Clock(clock)) = self.clock {
+            vm.set_clock(clock);
+        }
+        vm.set_entry(self.entry);
+        (vm, tx)
+    }
+}
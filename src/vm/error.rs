@@ -10,24 +10,69 @@ pub enum VmError {
     Message(String),
     #[error("Stack underflow")]
     StackUnderflow,
+    #[error("Stack overflow")]
+    StackOverflow,
     #[error("Stack underflow for {0}")]
     StackUnderflowFor(&'static str),
     #[error("Type mismatch in {0}")]
     TypeMismatch(&'static str),
     #[error("Division by zero")]
     DivisionByZero,
+    #[error("Integer overflow in {0}")]
+    IntegerOverflow(&'static str),
+    #[error("Negative shift count {0}")]
+    NegativeShift(i32),
+    #[error("Empty range {0}..{1}")]
+    EmptyRange(i32, i32),
+    #[error("Index out of bounds")]
+    IndexOutOfBounds,
+    #[error("Value {0} does not fit in a byte")]
+    ByteOutOfRange(i32),
+    #[error("Invalid code point {0}")]
+    InvalidCodePoint(i32),
     #[error("Execution out of bounds")]
     ExecutionOutOfBounds,
+    #[error("Subroutine at {0} did not leave exactly one value on the stack")]
+    UnbalancedSubroutine(usize),
+    #[error("Subroutine at {0} halted or yielded before returning")]
+    StoppedInSubroutine(usize),
+    #[error("Expected stack depth {expected}, found {actual}")]
+    UnexpectedDepth { expected: usize, actual: usize },
+    #[error("Program ended with {0} values on the stack")]
+    StackNotEmpty(usize),
+    #[error("Switch table entry at {0} is not a Jump")]
+    MalformedSwitchTable(usize),
     #[error("No bytecode to execute")]
     NoBytecode,
     #[error("Variable at index {0} not found")]
     VariableNotFound(usize),
     #[error("Invalid reference")]
     InvalidReference,
+    #[error("Invalid value encoding: {0}")]
+    InvalidEncoding(String),
+    #[error("Cannot serialize a value that contains itself")]
+    CyclicValue,
+    #[error("Actor limit of {0} exceeded")]
+    ActorLimitExceeded(usize),
+    /// The mailbox has no message right now, but more may still arrive.
     #[error("Mailbox empty")]
     MailboxEmpty,
+    /// Every sender is gone and the mailbox is drained, so no message will
+    /// ever arrive.
+    #[error("Mailbox closed")]
+    MailboxClosed,
+    #[error("Mailbox has no room for {0} messages")]
+    MailboxFull(usize),
+    #[error("Actor finished a pass without taking any of {0} queued messages")]
+    MessageNotConsumed(usize),
     #[error("Channel send error: {error}")]
     ChannelSend { error: String, value: Value },
+    #[error("Execution cancelled")]
+    Cancelled,
+    #[error("Assertion failed: {0}")]
+    AssertionFailed(String),
+    #[error("I/O error: {0}")]
+    Io(String),
     #[error("Compilation error: {0}")]
     CompilationError(#[from] CompilerError),
 }
@@ -38,6 +83,12 @@ impl From<String> for VmError {
     }
 }
 
+impl From<std::io::Error> for VmError {
+    fn from(err: std::io::Error) -> Self {
+        VmError::Io(err.to_string())
+    }
+}
+
 impl From<&str> for VmError {
     fn from(value: &str) -> Self {
         VmError::Message(value.to_string())
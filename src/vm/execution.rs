@@ -1,14 +1,67 @@
 // src/vm/execution.rs
 
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Instant;
 
 use crate::vm::error::VmError;
 use crate::vm::heap::Heap;
 use crate::vm::opcodes::OpCode;
+use crate::vm::rng::Rng;
 use crate::vm::value::Value;
+use crate::vm::vm::RunResult;
 
 use tokio::sync::mpsc::Receiver;
 
+/// Where a VM writes program output (`out`) and warnings (`err`).
+pub struct Output {
+    pub out: Box<dyn Write + Send>,
+    pub err: Box<dyn Write + Send>,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            out: Box::new(io::stdout()),
+            err: Box::new(io::stderr()),
+        }
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Output").finish_non_exhaustive()
+    }
+}
+
+/// A host callback that observes each message `ReceiveMessage` or
+/// `DrainMailbox` takes from the mailbox.
+pub struct MessageHook(pub Box<dyn FnMut(&Value) + Send>);
+
+impl fmt::Debug for MessageHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageHook").finish_non_exhaustive()
+    }
+}
+
+/// The time source behind `Now`, in milliseconds. Defaults to the time
+/// elapsed since the clock was created.
+pub struct Clock(pub Box<dyn Fn() -> u64 + Send>);
+
+impl Default for Clock {
+    fn default() -> Self {
+        let start = Instant::now();
+        Clock(Box::new(move || start.elapsed().as_millis() as u64))
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Clock").finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug)]
 pub struct ExecutionContext {
     pub stack: Vec<Value>,
@@ -16,6 +69,26 @@ pub struct ExecutionContext {
     pub ip: usize,
     pub call_stack: Vec<usize>,
     pub bytecode: Vec<OpCode>,
+    /// Maximum number of values the stack may hold, if bounded.
+    pub stack_capacity: Option<usize>,
+    /// Maximum number of actors and supervisors alive on the heap at once.
+    pub max_actors: Option<usize>,
+    /// Whether `LoadVar` of an unset local pushes `Null` instead of failing
+    /// with [`VmError::VariableNotFound`].
+    pub default_locals_to_null: bool,
+    pub output: Output,
+    pub on_message: Option<MessageHook>,
+    pub rng: Rng,
+    pub clock: Clock,
+    /// Set by `Halt` and `Yield` to end the current run early, and taken by
+    /// the VM when it returns.
+    pub stop: Option<RunResult>,
+    /// Messages `ReceiveMessage` may still take before the run yields, if
+    /// bounded.
+    pub message_budget: Option<usize>,
+    /// Messages `ReceiveMessage` and `DrainMailbox` have taken from the
+    /// mailbox so far.
+    pub messages_received: usize,
 }
 
 impl ExecutionContext {
@@ -26,6 +99,16 @@ impl ExecutionContext {
             ip: 0,
             call_stack: Vec::new(),
             bytecode,
+            stack_capacity: None,
+            max_actors: None,
+            default_locals_to_null: false,
+            output: Output::default(),
+            on_message: None,
+            rng: Rng::default(),
+            clock: Clock::default(),
+            stop: None,
+            message_budget: None,
+            messages_received: 0,
         }
     }
 
@@ -39,13 +122,31 @@ impl ExecutionContext {
             return Err(VmError::ExecutionOutOfBounds);
         }
 
-        let opcode = self.bytecode[self.ip].clone();
+        let opcode = self.bytecode[self.ip];
         // advance instruction pointer unless opcode modified it
         self.ip += 1;
         log::info!("Executing opcode: {:?}", opcode);
         opcode.execute(self, heap, mailbox).await
     }
 
+    /// Executes opcodes back to back until reaching an async one (see
+    /// [`OpCode::is_async`]), the end of the bytecode, or a `Halt` or
+    /// `Yield`, without an await point in between.
+    pub fn run_sync(&mut self, heap: &mut Heap) -> Result<(), VmError> {
+        while let Some(&opcode) = self.bytecode.get(self.ip) {
+            if opcode.is_async() {
+                break;
+            }
+            self.ip += 1;
+            log::info!("Executing opcode: {:?}", opcode);
+            opcode.execute_sync(self, heap)?;
+            if self.stop.is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
     pub fn ip(&self) -> usize {
         self.ip
     }
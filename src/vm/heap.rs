@@ -3,13 +3,32 @@
 use crate::vm::error::VmError;
 use crate::vm::value::Value;
 use crate::vm::VM;
-use std::collections::HashMap;
-use tokio::sync::mpsc::Sender;
+use num_bigint::BigInt;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+/// Bits of an address that select the slot; the bits above them hold the
+/// slot's generation.
+const SLOT_BITS: u32 = usize::BITS / 2;
+
+/// The highest generation a slot reaches. A slot collected at this
+/// generation is retired instead of wrapping back to generation zero, where
+/// its addresses would alias those of its first occupant.
+const MAX_GENERATION: usize = usize::MAX >> SLOT_BITS;
 
 #[derive(Debug)]
 pub struct Heap {
+    /// Objects keyed by their full address, generation included.
     objects: HashMap<usize, HeapObject>,
+    /// The first slot that has never been used.
     next_address: usize,
+    /// Addresses of collected objects, whose slots are handed out again
+    /// under the next generation before new slots are used.
+    free: Vec<usize>,
+    /// Number of `Actor` and `Supervisor` objects currently on the heap.
+    actors: usize,
 }
 
 #[derive(Debug)]
@@ -23,6 +42,13 @@ pub struct NativeFunction {
 pub enum HeapObject {
     Array(Vec<Value>, usize),
     String(String, usize),
+    Bytes(Vec<u8>, usize),
+    /// An integer too large for `Value::Integer`.
+    BigInt(BigInt, usize),
+    Set(HashSet<Value>, usize),
+    /// An integer updated atomically. Copies share the count with the
+    /// original, so actors started with `Fork` update the same counter.
+    Counter(Arc<AtomicI64>, usize),
     Module {
         name: String,
         exports: HashMap<String, Value>,
@@ -31,6 +57,15 @@ pub enum HeapObject {
     NativeFunction(NativeFunction, usize),
     Actor(VM, Sender<Value>, usize),
     Supervisor(VM, Sender<Value>, usize),
+    /// A message queue owned by the program, written with `SendMessage` and
+    /// read with `Select`.
+    Channel(Sender<Value>, Receiver<Value>, usize),
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Heap {
@@ -38,17 +73,66 @@ impl Heap {
         Self {
             objects: HashMap::new(),
             next_address: 0,
+            free: Vec::new(),
+            actors: 0,
+        }
+    }
+
+    /// A heap with room for `capacity` objects before it has to grow.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            objects: HashMap::with_capacity(capacity),
+            ..Self::new()
         }
     }
 
+    /// Number of objects the heap can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.objects.capacity()
+    }
+
+    /// Makes room for at least `additional` more objects.
+    pub fn reserve(&mut self, additional: usize) {
+        self.objects.reserve(additional);
+    }
+
+    /// Stores `object` and returns its address, reusing the slot of a
+    /// collected object when there is one.
+    ///
+    /// An address carries its slot's generation, which goes up each time the
+    /// slot is reused. A stale reference to a collected object therefore
+    /// never aliases the new occupant: the heap reports it as invalid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every slot an address can select has been used, which is
+    /// 2^16 slots on 32-bit targets and 2^32 on 64-bit ones.
     pub fn allocate(&mut self, object: HeapObject) -> usize {
-        let address = self.next_address;
+        let address = match self.free.pop() {
+            Some(freed) => freed + (1 << SLOT_BITS),
+            None => {
+                assert!(
+                    self.next_address < 1 << SLOT_BITS,
+                    "heap exhausted: all {} slots have been used",
+                    1usize << SLOT_BITS
+                );
+                self.next_address += 1;
+                self.next_address - 1
+            }
+        };
+        if object.is_actor() {
+            self.actors += 1;
+        }
         self.objects.insert(address, object);
         log::info!("Allocated object at address {}", address);
-        self.next_address += 1;
         address
     }
 
+    /// The slot an address refers to, ignoring its generation.
+    pub fn slot(address: usize) -> usize {
+        address & ((1 << SLOT_BITS) - 1)
+    }
+
     pub fn get(&self, address: usize) -> Option<&HeapObject> {
         if let Some(obj) = self.objects.get(&address) {
             Some(obj)
@@ -58,63 +142,244 @@ impl Heap {
         }
     }
 
+    /// Number of objects currently held by the heap, including unreferenced
+    /// ones that have not been collected yet.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
     pub fn get_mut(&mut self, address: usize) -> Option<&mut HeapObject> {
         self.objects.get_mut(&address)
     }
 
+    /// Removes the object at `address` regardless of its reference count.
+    /// Any remaining references to it become invalid.
+    pub fn remove(&mut self, address: usize) -> Option<HeapObject> {
+        let object = self.objects.remove(&address)?;
+        if object.is_actor() {
+            self.actors -= 1;
+        }
+        Some(object)
+    }
+
+    /// Replaces every object a snapshot can copy with `objects`, as captured
+    /// in a snapshot. Objects it cannot copy (see [`HeapObject::try_clone`]),
+    /// such as actors, modules, native functions, and channels, are left in
+    /// place unless an object is restored in the same slot, whatever the
+    /// generation; the restored object wins, since each slot holds one.
+    pub fn restore(&mut self, objects: impl IntoIterator<Item = (usize, HeapObject)>) {
+        self.objects.retain(|_, obj| !obj.is_copyable());
+        let kept: HashMap<usize, usize> = self
+            .objects
+            .keys()
+            .map(|&address| (Self::slot(address), address))
+            .collect();
+        for (address, object) in objects {
+            if let Some(&stale) = kept.get(&Self::slot(address)) {
+                self.remove(stale);
+            }
+            self.objects.insert(address, object);
+            self.next_address = self.next_address.max(Self::slot(address) + 1);
+        }
+        let taken: HashSet<usize> = self
+            .objects
+            .keys()
+            .map(|&address| Self::slot(address))
+            .collect();
+        self.free
+            .retain(|&address| !taken.contains(&Self::slot(address)));
+    }
+
+    /// Removes every actor and supervisor regardless of reference counts,
+    /// dropping their VMs so their mailboxes close and further sends to
+    /// them fail. References to them become invalid. Dropping the heap has
+    /// the same effect on its actors.
+    pub fn shutdown(&mut self) {
+        let before = self.objects.len();
+        self.objects.retain(|_, obj| !obj.is_actor());
+        self.actors = 0;
+        log::info!("Shut down {} actors", before - self.objects.len());
+    }
+
+    /// Number of live actors and supervisors, which stop counting once
+    /// collected or removed.
+    pub fn actor_count(&self) -> usize {
+        self.actors
+    }
+
+    /// Iterates over every object on the heap in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &HeapObject)> {
+        self.objects
+            .iter()
+            .map(|(address, object)| (*address, object))
+    }
+
     pub fn collect_garbage(&mut self) {
         let before = self.objects.len();
-        self.objects.retain(|_, obj| obj.is_alive());
+        let actors = &mut self.actors;
+        let free = &mut self.free;
+        self.objects.retain(|&address, obj| {
+            let alive = obj.is_alive();
+            if !alive {
+                if obj.is_actor() {
+                    *actors -= 1;
+                }
+                if address >> SLOT_BITS < MAX_GENERATION {
+                    free.push(address);
+                }
+            }
+            alive
+        });
         let collected = before - self.objects.len();
         if collected > 0 {
             log::info!("Collected {} unreachable heap objects", collected);
         }
     }
+
+    /// Addresses of every live object reachable from `roots` through arrays,
+    /// sets, and module exports. Each object is visited once, so cycles are
+    /// fine, and references to addresses no longer on the heap are skipped.
+    /// Actors and supervisors are leaves: their VMs have heaps of their own.
+    pub fn reachable_from(&self, roots: &[Value]) -> HashSet<usize> {
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<Value> = roots.to_vec();
+        while let Some(value) = pending.pop() {
+            let Value::Reference(address) = value else {
+                continue;
+            };
+            let Some(object) = self.objects.get(&address) else {
+                continue;
+            };
+            if !reachable.insert(address) {
+                continue;
+            }
+            pending.extend(object.contents());
+        }
+        reachable
+    }
 }
 
 impl HeapObject {
-    pub fn is_alive(&self) -> bool {
+    pub fn ref_count(&self) -> usize {
         match self {
             HeapObject::Array(_, rc)
             | HeapObject::String(_, rc)
+            | HeapObject::Bytes(_, rc)
+            | HeapObject::BigInt(_, rc)
+            | HeapObject::Set(_, rc)
+            | HeapObject::Counter(_, rc)
             | HeapObject::NativeFunction(_, rc)
             | HeapObject::Actor(_, _, rc)
-            | HeapObject::Supervisor(_, _, rc) => *rc > 0,
-            HeapObject::Module { ref_count, .. } => *ref_count > 0,
+            | HeapObject::Supervisor(_, _, rc)
+            | HeapObject::Channel(_, _, rc) => *rc,
+            HeapObject::Module { ref_count, .. } => *ref_count,
         }
     }
 
-    pub fn increment_ref(&mut self) {
+    fn ref_count_mut(&mut self) -> &mut usize {
         match self {
             HeapObject::Array(_, rc)
             | HeapObject::String(_, rc)
+            | HeapObject::Bytes(_, rc)
+            | HeapObject::BigInt(_, rc)
+            | HeapObject::Set(_, rc)
+            | HeapObject::Counter(_, rc)
             | HeapObject::NativeFunction(_, rc)
             | HeapObject::Actor(_, _, rc)
-            | HeapObject::Supervisor(_, _, rc) => *rc += 1,
-            HeapObject::Module {
-                ref mut ref_count, ..
-            } => *ref_count += 1,
+            | HeapObject::Supervisor(_, _, rc)
+            | HeapObject::Channel(_, _, rc) => rc,
+            HeapObject::Module { ref_count, .. } => ref_count,
         }
     }
 
+    /// A copy of a plain data object (array, string, bytes, big integer, or
+    /// set) or of a counter, keeping its reference count. Objects that own a
+    /// VM or native code cannot be copied and yield `None`.
+    pub fn try_clone(&self) -> Option<HeapObject> {
+        match self {
+            HeapObject::Array(items, rc) => Some(HeapObject::Array(items.clone(), *rc)),
+            HeapObject::String(text, rc) => Some(HeapObject::String(text.clone(), *rc)),
+            HeapObject::Bytes(bytes, rc) => Some(HeapObject::Bytes(bytes.clone(), *rc)),
+            HeapObject::BigInt(value, rc) => Some(HeapObject::BigInt(value.clone(), *rc)),
+            HeapObject::Set(items, rc) => Some(HeapObject::Set(items.clone(), *rc)),
+            HeapObject::Counter(count, rc) => Some(HeapObject::Counter(Arc::clone(count), *rc)),
+            HeapObject::Module { .. }
+            | HeapObject::NativeFunction(..)
+            | HeapObject::Actor(..)
+            | HeapObject::Supervisor(..)
+            | HeapObject::Channel(..) => None,
+        }
+    }
+
+    /// Whether [`HeapObject::try_clone`] can copy the object.
+    fn is_copyable(&self) -> bool {
+        !matches!(
+            self,
+            HeapObject::Module { .. }
+                | HeapObject::NativeFunction(..)
+                | HeapObject::Actor(..)
+                | HeapObject::Supervisor(..)
+                | HeapObject::Channel(..)
+        )
+    }
+
+    fn is_actor(&self) -> bool {
+        matches!(self, HeapObject::Actor(..) | HeapObject::Supervisor(..))
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.ref_count() > 0
+    }
+
+    /// The values the object holds on to, each of which owns one count of
+    /// whatever it refers to: array and set elements and module exports.
+    pub fn contents(&self) -> Box<dyn Iterator<Item = &Value> + '_> {
+        match self {
+            HeapObject::Array(items, _) => Box::new(items.iter()),
+            HeapObject::Set(items, _) => Box::new(items.iter()),
+            HeapObject::Module { exports, .. } => Box::new(exports.values()),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+
+    pub fn increment_ref(&mut self) {
+        *self.ref_count_mut() += 1;
+    }
+
     pub fn decrement_ref(&mut self) {
+        let rc = self.ref_count_mut();
+        *rc = rc.saturating_sub(1);
+    }
+
+    /// A one-line description of the object used in heap dumps.
+    pub fn summary(&self) -> String {
         match self {
-            HeapObject::Array(_, rc)
-            | HeapObject::String(_, rc)
-            | HeapObject::NativeFunction(_, rc)
-            | HeapObject::Actor(_, _, rc)
-            | HeapObject::Supervisor(_, _, rc) => {
-                if *rc > 0 {
-                    *rc -= 1;
+            HeapObject::Array(items, _) => format!("Array len={}", items.len()),
+            HeapObject::String(text, _) => {
+                let preview: String = text.chars().take(16).collect();
+                if preview.len() < text.len() {
+                    format!("String {:?}...", preview)
+                } else {
+                    format!("String {:?}", preview)
                 }
             }
-            HeapObject::Module {
-                ref mut ref_count, ..
-            } => {
-                if *ref_count > 0 {
-                    *ref_count -= 1;
-                }
+            HeapObject::Bytes(bytes, _) => format!("Bytes len={}", bytes.len()),
+            HeapObject::BigInt(value, _) => format!("BigInt {}", value),
+            HeapObject::Set(items, _) => format!("Set len={}", items.len()),
+            HeapObject::Counter(count, _) => format!("Counter {}", count.load(Ordering::SeqCst)),
+            HeapObject::Module { name, exports, .. } => {
+                format!("Module {} exports={}", name, exports.len())
+            }
+            HeapObject::NativeFunction(function, _) => {
+                format!("NativeFunction {}/{}", function.name, function.arity)
             }
+            HeapObject::Actor(vm, _, _) => format!("Actor ip={}", vm.ip()),
+            HeapObject::Supervisor(vm, _, _) => format!("Supervisor ip={}", vm.ip()),
+            HeapObject::Channel(_, receiver, _) => format!("Channel queued={}", receiver.len()),
         }
     }
 }
@@ -1,18 +1,26 @@
 // src/vm/mod.rs
 
+pub mod builder;
 pub mod error;
 pub mod execution;
 pub mod heap;
 pub mod opcodes;
+pub mod rng;
+pub mod snapshot;
+pub mod symbol;
 pub mod value;
+#[allow(clippy::module_inception)]
 pub mod vm;
 
+pub use crate::vm::builder::VmBuilder;
 pub use crate::vm::error::VmError;
-pub use crate::vm::execution::ExecutionContext;
+pub use crate::vm::execution::{Clock, ExecutionContext, MessageHook, Output};
 pub use crate::vm::heap::{Heap, HeapObject};
 pub use crate::vm::opcodes::OpCode;
+pub use crate::vm::snapshot::VmSnapshot;
+pub use crate::vm::symbol::Symbol;
 pub use crate::vm::value::Value;
-pub use crate::vm::vm::VM;
+pub use crate::vm::vm::{RunResult, StepAction, VM};
 
 #[cfg(test)]
 mod tests {
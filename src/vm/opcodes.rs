@@ -1,11 +1,21 @@
 // src/vm/opcodes.rs
 
 use crate::vm::error::VmError;
-use crate::vm::execution::ExecutionContext;
+use crate::vm::execution::{ExecutionContext, MessageHook};
 use crate::vm::heap::{Heap, HeapObject};
+use crate::vm::snapshot::VmSnapshot;
+use crate::vm::symbol::Symbol;
 use crate::vm::value::Value;
-use crate::vm::vm::VM;
-use tokio::sync::mpsc::Receiver;
+use crate::vm::vm::{RunResult, VM};
+use num_bigint::BigInt;
+use std::collections::HashSet;
+use std::future::poll_fn;
+use std::io::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender};
 
 fn unary_op<F>(stack: &mut Vec<Value>, f: F) -> Result<(), VmError>
 where
@@ -36,6 +46,68 @@ where
     Ok(())
 }
 
+/// An integer operand as a big integer: either a plain `Integer` or a
+/// reference to a `BigInt` heap object.
+fn big_operand(heap: &Heap, value: Value) -> Option<BigInt> {
+    match value {
+        Value::Integer(i) => Some(BigInt::from(i)),
+        Value::Reference(address) => match heap.get(address) {
+            Some(HeapObject::BigInt(value, _)) => Some(value.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Pushes `value` as an `Integer` if it fits, otherwise as a new `BigInt`.
+fn push_big(
+    execution: &mut ExecutionContext,
+    heap: &mut Heap,
+    value: BigInt,
+) -> Result<(), VmError> {
+    let value = match i32::try_from(&value) {
+        Ok(small) => Value::Integer(small),
+        Err(_) => Value::Reference(heap.allocate(HeapObject::BigInt(value, 0))),
+    };
+    push_value(execution, heap, value)
+}
+
+/// Integer arithmetic that falls back to big integers when an operand is one
+/// or the `i32` result overflows. Everything else goes through `small`.
+fn big_binary_op(
+    execution: &mut ExecutionContext,
+    heap: &mut Heap,
+    opcode: &'static str,
+    small: fn(Value, Value) -> Result<Value, VmError>,
+    big: fn(BigInt, BigInt) -> BigInt,
+) -> Result<(), VmError> {
+    let len = execution.stack.len();
+    if len < 2 {
+        return binary_op(&mut execution.stack, small);
+    }
+    let (a, b) = (execution.stack[len - 2], execution.stack[len - 1]);
+    let is_big = |value| match value {
+        Value::Reference(address) => matches!(heap.get(address), Some(HeapObject::BigInt(..))),
+        _ => false,
+    };
+    if !is_big(a) && !is_big(b) {
+        match small(a, b) {
+            Err(VmError::IntegerOverflow(_)) => {}
+            result => {
+                execution.stack.truncate(len - 2);
+                execution.stack.push(result?);
+                return Ok(());
+            }
+        }
+    }
+    let (Some(x), Some(y)) = (big_operand(heap, a), big_operand(heap, b)) else {
+        return Err(VmError::TypeMismatch(opcode));
+    };
+    pop_value(execution, heap)?;
+    pop_value(execution, heap)?;
+    push_big(execution, heap, big(x, y))
+}
+
 fn increment_reference(heap: &mut Heap, address: usize) -> Result<(), VmError> {
     if let Some(object) = heap.get_mut(address) {
         object.increment_ref();
@@ -59,6 +131,7 @@ fn push_value(
     heap: &mut Heap,
     value: Value,
 ) -> Result<(), VmError> {
+    check_stack_capacity(execution)?;
     if let Value::Reference(address) = value {
         increment_reference(heap, address)?;
     }
@@ -66,6 +139,25 @@ fn push_value(
     Ok(())
 }
 
+/// Pushes a value whose reference, if any, already carries a count for the
+/// stack, so ownership moves onto the stack without touching the heap.
+fn push_owned(execution: &mut ExecutionContext, value: Value) -> Result<(), VmError> {
+    check_stack_capacity(execution)?;
+    execution.stack.push(value);
+    Ok(())
+}
+
+fn check_stack_capacity(execution: &ExecutionContext) -> Result<(), VmError> {
+    if execution
+        .stack_capacity
+        .is_some_and(|capacity| execution.stack.len() >= capacity)
+    {
+        log::error!("Stack overflow at {} values", execution.stack.len());
+        return Err(VmError::StackOverflow);
+    }
+    Ok(())
+}
+
 fn pop_value(execution: &mut ExecutionContext, heap: &mut Heap) -> Result<Value, VmError> {
     if let Some(value) = execution.stack.pop() {
         if let Value::Reference(address) = value {
@@ -77,45 +169,788 @@ fn pop_value(execution: &mut ExecutionContext, heap: &mut Heap) -> Result<Value,
     }
 }
 
+fn counter(heap: &Heap, counter_ref: Value) -> Result<&AtomicI64, VmError> {
+    if let Value::Reference(address) = counter_ref {
+        if let Some(HeapObject::Counter(count, _)) = heap.get(address) {
+            return Ok(count);
+        }
+    }
+    Err(VmError::InvalidReference)
+}
+
+fn set_contents(heap: &Heap, set_ref: Value) -> Result<&HashSet<Value>, VmError> {
+    if let Value::Reference(address) = set_ref {
+        if let Some(HeapObject::Set(set, _)) = heap.get(address) {
+            return Ok(set);
+        }
+    }
+    Err(VmError::InvalidReference)
+}
+
+fn array_contents(heap: &Heap, array_ref: Value) -> Result<&Vec<Value>, VmError> {
+    if let Value::Reference(address) = array_ref {
+        if let Some(HeapObject::Array(items, _)) = heap.get(address) {
+            return Ok(items);
+        }
+    }
+    Err(VmError::InvalidReference)
+}
+
+fn bytes_mut(heap: &mut Heap, bytes_ref: Value) -> Result<&mut Vec<u8>, VmError> {
+    if let Value::Reference(address) = bytes_ref {
+        if let Some(HeapObject::Bytes(bytes, _)) = heap.get_mut(address) {
+            return Ok(bytes);
+        }
+    }
+    Err(VmError::InvalidReference)
+}
+
+/// Checks that a shift has an integer operand and a non-negative count.
+fn shift_operands(value: Value, count: Value, opcode: &'static str) -> Result<(i32, u32), VmError> {
+    match (value, count) {
+        (Value::Integer(value), Value::Integer(count)) => u32::try_from(count)
+            .map(|count| (value, count))
+            .map_err(|_| VmError::NegativeShift(count)),
+        _ => Err(VmError::TypeMismatch(opcode)),
+    }
+}
+
+/// The address `offset` away from the instruction being executed, whose
+/// address is one before `ip`. An offset that leaves the address space
+/// yields `usize::MAX`, which every bounds check rejects.
+fn relative_target(execution: &ExecutionContext, offset: isize) -> usize {
+    (execution.ip - 1)
+        .checked_add_signed(offset)
+        .unwrap_or(usize::MAX)
+}
+
+/// Fails unless `target` is an instruction address or the bytecode length,
+/// the address just past the end; see `OpCode::Jump`.
+fn check_target(
+    execution: &ExecutionContext,
+    target: usize,
+    opcode: &'static str,
+) -> Result<(), VmError> {
+    if target > execution.bytecode.len() {
+        log::error!(
+            "{} target {} out of bounds (bytecode length {})",
+            opcode,
+            target,
+            execution.bytecode.len()
+        );
+        return Err(VmError::ExecutionOutOfBounds);
+    }
+    Ok(())
+}
+
+/// Pops a boolean and jumps to `target` if it equals `when`. Any other value
+/// is a type mismatch, and is dropped like every popped operand.
+fn jump_if(
+    execution: &mut ExecutionContext,
+    heap: &mut Heap,
+    target: usize,
+    when: bool,
+    opcode: &'static str,
+) -> Result<(), VmError> {
+    match pop_value(execution, heap)? {
+        Value::Boolean(condition) if condition == when => {
+            check_target(execution, target, opcode)?;
+            execution.ip = target;
+            Ok(())
+        }
+        Value::Boolean(_) => Ok(()),
+        _ => Err(VmError::TypeMismatch(opcode)),
+    }
+}
+
+/// Pops a supervisor reference, sets its strategy, and pushes the reference
+/// back.
+fn set_strategy(
+    execution: &mut ExecutionContext,
+    heap: &mut Heap,
+    strategy: usize,
+) -> Result<(), VmError> {
+    let sup_ref = pop_value(execution, heap)?;
+    if let Value::Reference(addr) = sup_ref {
+        if let Some(HeapObject::Supervisor(vm, _, _)) = heap.get_mut(addr) {
+            vm.set_strategy(strategy);
+        } else {
+            return Err(VmError::InvalidReference);
+        }
+        push_value(execution, heap, Value::Reference(addr))
+    } else {
+        Err(VmError::InvalidReference)
+    }
+}
+
+/// Checks that `index` is an integer in `0..len`.
+fn index_operand(index: Value, len: usize, opcode: &'static str) -> Result<usize, VmError> {
+    let Value::Integer(index) = index else {
+        return Err(VmError::TypeMismatch(opcode));
+    };
+    usize::try_from(index)
+        .ok()
+        .filter(|&index| index < len)
+        .ok_or_else(|| {
+            log::error!(
+                "{} index {} out of bounds for length {}",
+                opcode,
+                index,
+                len
+            );
+            VmError::IndexOutOfBounds
+        })
+}
+
+fn check_actor_limit(execution: &ExecutionContext, heap: &Heap) -> Result<(), VmError> {
+    match execution.max_actors {
+        Some(max) if heap.actor_count() >= max => {
+            log::error!("Cannot spawn: {} actors already alive", heap.actor_count());
+            Err(VmError::ActorLimitExceeded(max))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Creates a VM for a spawned child, running the same bytecode under the
+/// same actor and stack limits, so that a child cannot escape the cap its
+/// parent runs under.
+fn child_vm(execution: &ExecutionContext) -> (VM, Sender<Value>) {
+    let (mut vm, tx) = VM::new(execution.bytecode.clone(), None);
+    if let Some(max_actors) = execution.max_actors {
+        vm = vm.with_max_actors(max_actors);
+    }
+    if let Some(capacity) = execution.stack_capacity {
+        vm = vm.with_stack_capacity(capacity);
+    }
+    (vm, tx)
+}
+
+/// Calls the subroutine at `addr` with `arguments` pushed as its inputs, in
+/// order, and runs it until it returns, yielding the single value it leaves
+/// behind in their place.
+///
+/// A `Halt` or `Yield` inside the subroutine cannot stop the run halfway
+/// through the calling opcode, so it fails with
+/// [`VmError::StoppedInSubroutine`] after unwinding the subroutine's frames.
+async fn call_subroutine(
+    execution: &mut ExecutionContext,
+    heap: &mut Heap,
+    mailbox: &mut Receiver<Value>,
+    addr: usize,
+    arguments: &[Value],
+) -> Result<Value, VmError> {
+    if addr >= execution.bytecode.len() {
+        log::error!(
+            "Subroutine {} out of bounds (bytecode length {})",
+            addr,
+            execution.bytecode.len()
+        );
+        return Err(VmError::ExecutionOutOfBounds);
+    }
+    let depth = execution.stack.len();
+    let frames = execution.call_stack.len();
+    for &argument in arguments {
+        push_value(execution, heap, argument)?;
+    }
+    execution.call_stack.push(execution.ip);
+    execution.ip = addr;
+    while execution.call_stack.len() > frames {
+        Box::pin(execution.step(heap, mailbox)).await?;
+        if execution.stop.take().is_some() {
+            log::error!("Subroutine {} halted or yielded before returning", addr);
+            execution.ip = execution.call_stack[frames];
+            execution.call_stack.truncate(frames);
+            return Err(VmError::StoppedInSubroutine(addr));
+        }
+    }
+    if execution.stack.len() != depth + 1 {
+        log::error!(
+            "Subroutine {} left the stack at depth {}, expected {}",
+            addr,
+            execution.stack.len(),
+            depth + 1
+        );
+        return Err(VmError::UnbalancedSubroutine(addr));
+    }
+    pop_value(execution, heap)
+}
+
+/// Pops two set references and pushes a freshly allocated set built from
+/// them. Every reference held by the new set counts as a new owner.
+fn set_operation<F>(execution: &mut ExecutionContext, heap: &mut Heap, f: F) -> Result<(), VmError>
+where
+    F: Fn(&HashSet<Value>, &HashSet<Value>) -> HashSet<Value>,
+{
+    let b = pop_value(execution, heap)?;
+    let a = pop_value(execution, heap)?;
+    let result = f(set_contents(heap, a)?, set_contents(heap, b)?);
+    for value in &result {
+        if let Value::Reference(address) = value {
+            increment_reference(heap, *address)?;
+        }
+    }
+    let address = heap.allocate(HeapObject::Set(result, 0));
+    push_value(execution, heap, Value::Reference(address))
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum OpCode {
     // Variables
     StoreVar(usize),
     LoadVar(usize),
+    /// Unsets a local, releasing the reference it held. Clearing an unset
+    /// local does nothing.
+    ClearVar(usize),
+    /// Stores the top value in a local without popping it, like
+    /// `Dup StoreVar n`.
+    Tee(usize),
+    /// Like `StoreVar`, but fails with [`VmError::VariableNotFound`] unless
+    /// the local is already set, for replacing a value rather than creating
+    /// one. The reference it held is released.
+    UpdateVar(usize),
 
     // Stack
+    Nop,
     PushConst(Value),
+    /// Allocates a new heap string with the given contents and pushes a
+    /// reference to it.
+    PushString(Symbol),
+    /// Allocates a big integer parsed from the given decimal digits and
+    /// pushes a reference to it.
+    PushBigInt(Symbol),
     Pop,
     Dup,
     Swap,
+    /// Pops a boolean condition and then two values, and pushes the deeper
+    /// value if the condition is true or the top one if it is false:
+    /// `then else condition CMov` is a branch-free ternary.
+    CMov,
+    SwapN(usize, usize),
 
     // Arithmetic
     Add,
     Sub,
     Mul,
     Div,
+    /// Like `Div`, but pushes `Null` instead of failing on a zero divisor.
+    SafeDiv,
     Mod,
     Neg,
     Exp,
+    /// Pops a shift count and an integer, and pushes the integer shifted
+    /// left. Counts of 32 or more shift every bit out and yield 0.
+    Shl,
+    /// Pops a shift count and an integer, and pushes the integer shifted
+    /// right arithmetically. Counts of 32 or more leave only the sign: 0 for
+    /// non-negative integers, -1 for negative ones.
+    Shr,
+    /// Pops a value and pushes its truthiness: booleans are unchanged,
+    /// integers and floats are `true` unless zero, references are `true`,
+    /// and `Null` is `false`.
+    ToBool,
+    /// Pops a tolerance and then two numbers, and pushes whether the numbers
+    /// differ by less than the tolerance. Integers are compared as floats.
+    FloatEq,
 
     // Control Flow
+    /// Continues at the target address.
+    ///
+    /// Every opcode with an address operand accepts any instruction address
+    /// or the bytecode length, the address just past the last instruction.
+    /// Jumping or calling there ends the program as if it ran off the end,
+    /// and an actor spawned there finishes as soon as it runs. Anything
+    /// beyond fails with [`VmError::ExecutionOutOfBounds`]. `ArrayMap`,
+    /// `ArrayFilter`, and `ArrayReduce` are the exception: their subroutines
+    /// must return a value, so they need an instruction to start at.
     Jump(usize),
     JumpIfFalse(usize),
+    JumpIfTrue(usize),
+    /// Pops an integer and jumps to it as an absolute address, for computed
+    /// gotos. Negative and out-of-range targets fail with
+    /// [`VmError::ExecutionOutOfBounds`].
+    JumpDyn,
+    /// `Jump` to an offset from this instruction's own address rather than
+    /// to an absolute one, so the code keeps working wherever it is placed.
+    JumpRel(isize),
+    /// `JumpIfFalse` with a relative target, as for `JumpRel`.
+    JumpIfFalseRel(isize),
+    /// `JumpIfTrue` with a relative target, as for `JumpRel`.
+    JumpIfTrueRel(isize),
     Call(usize),
+    TailCall(usize),
     Return,
+    /// Ends the run with [`RunResult::Halted`].
+    Halt,
+    /// Ends the run with [`RunResult::Yielded`]; running the VM again
+    /// continues with the next instruction.
+    Yield,
+    /// Pushes the number of calls that have not returned yet: `0` at the top
+    /// level, `1` inside a subroutine, and so on.
+    CallDepth,
+    /// Pops an integer index and jumps through an inline table: the `n`
+    /// opcodes following the switch must be `Jump`s, and index `i` continues
+    /// at the target of the `i`th one. An index outside `0..n` falls through
+    /// to the instruction after the table.
+    Switch(usize),
 
     // Actors
     SpawnActor(usize),
+    /// Like `SpawnActor`, but the actor starts with a copy of this program's
+    /// stack, locals, and call stack, as a [`VmSnapshot`] would restore
+    /// them: data objects are copied into its heap at the same addresses,
+    /// while references to actors and other uncopyable objects are invalid
+    /// there.
+    Fork(usize),
+    /// Like `Fork`, but runs the child on its own tokio task right away and
+    /// pushes nothing. The task keeps running until the child finishes;
+    /// errors are logged, since there is no one left to report them to.
+    ///
+    /// The child inherits the actor and stack limits, and spawning is refused
+    /// once the heap holds the maximum number of actors. The task itself is
+    /// not counted afterwards: it never lives on the heap, and nothing is
+    /// told when it finishes, so like an actor handed out by
+    /// [`VM::take_actor`] it stops counting once it is gone.
+    SpawnDetached(usize),
+    /// Pops an actor reference and a message, and sends the message to the
+    /// actor. A reference message keeps one count while it is queued. A
+    /// channel is accepted in place of the actor; a full one fails with
+    /// [`VmError::MailboxFull`] instead of waiting, as with `ChannelSend`.
     SendMessage,
+    /// Pushes the next message from the mailbox, moving the count a queued
+    /// reference carries onto the stack. Waits while the mailbox is empty
+    /// and fails with [`VmError::MailboxClosed`] once every sender is gone.
     ReceiveMessage,
+    /// Pushes a new array holding every message queued in the mailbox, in
+    /// arrival order, without waiting for more. The array is empty if the
+    /// mailbox is. A message referring to a missing object fails with
+    /// [`VmError::InvalidReference`], releasing the messages drained before it.
+    DrainMailbox,
+    /// Like `SendMessage`, but pushes whether the message was delivered
+    /// instead of failing when the actor is gone, its mailbox is closed, or
+    /// a target channel is full.
+    SendIfAlive,
+    /// Pushes a reference to a new channel, which `SendMessage` accepts in
+    /// place of an actor.
+    NewChannel,
+    /// Pops a channel reference and a message, and queues the message on
+    /// the channel. Fails with [`VmError::MailboxFull`] rather than waiting,
+    /// since only this program can make room.
+    ChannelSend,
+    /// Pops a channel reference and pushes the oldest message queued on it.
+    /// A channel keeps its own sender, so it never closes while reachable;
+    /// an empty one fails with [`VmError::MailboxEmpty`] rather than waiting
+    /// for a message nothing else could send.
+    ChannelRecv,
+    /// Pops an array of channel references and waits for the first message
+    /// on any of them, then pushes the message and the index of its channel.
+    /// Fails with [`VmError::MailboxClosed`] if every channel is closed.
+    Select,
+    /// Pops an actor reference and an array, sends the array's elements to
+    /// the actor in order, and pushes the actor reference back. Mailbox room
+    /// for the whole batch is reserved first, so either every message is
+    /// sent or none is: on failure the operands are left on the stack.
+    SendAll,
+    /// Pops an actor reference and runs the actor to completion, pushing
+    /// `Null` on success or a reference to a string describing its fault.
+    JoinActor,
 
     // Supervisor
     SpawnSupervisor(usize),
     SetStrategy(usize),
+    /// Like `SetStrategy`, but pops the strategy from the stack above the
+    /// supervisor reference.
+    SetStrategyDyn,
+    /// Pops a supervisor reference and pushes its current strategy.
+    GetStrategy,
     RestartChild(usize),
+
+    // Bytes
+    /// Pops a length and pushes a reference to that many zero bytes.
+    NewBytes,
+    /// Pops an index and a bytes reference, and pushes the byte at the index.
+    BytesGet,
+    /// Pops a bytes reference and pushes its length.
+    BytesLen,
+    /// Pops a byte value, an index, and a bytes reference, stores the byte at
+    /// the index, and pushes the bytes reference back.
+    BytesSet,
+    /// Pops a value and pushes a reference to bytes encoding it along with
+    /// the strings and arrays it refers to; see [`Value::serialize`].
+    Serialize,
+    /// Pops a bytes reference and pushes the value it encodes, rebuilding
+    /// its strings and arrays in this VM's heap; see [`Value::deserialize`].
+    Deserialize,
+
+    // Sets
+    NewSet,
+    SetAdd,
+    SetHas,
+    SetUnion,
+    SetIntersect,
+
+    // Counters
+    /// Pushes a reference to a new counter holding zero.
+    NewCounter,
+    /// Pops a counter reference, atomically adds one to it, and pushes the
+    /// new count.
+    CounterInc,
+    /// Pops a counter reference and pushes its count.
+    CounterGet,
+
+    // Arrays
+    NewArray,
+    /// Pops a value and an array reference, appends the value, and pushes
+    /// the array reference back.
+    ArrayPush,
+    /// Pops the top `n` values into a new array, deepest first, and pushes a
+    /// reference to it.
+    Collect(usize),
+    /// Pops an array reference and pushes its elements in order, the
+    /// inverse of `Collect`.
+    Spread,
+    /// Pops an end index, a start index, and an array reference, and pushes
+    /// a new array holding the elements in `start..end`.
+    ArraySlice,
+    /// Pops a string reference and pushes a new array of its Unicode code
+    /// points (not UTF-8 bytes) as integers.
+    StrCodePoints,
+    /// Pops a reference to a one-element array or a one-character string
+    /// and pushes the element or the character's code point. Any other
+    /// value fails with [`VmError::TypeMismatch`].
+    Unbox,
+    /// Pops an array of integer code points and pushes a new string built
+    /// from them.
+    ArrayToStr,
+    /// Pops an array reference and calls the subroutine at the address once
+    /// per element, with the element as its only input. The value each call
+    /// leaves on the stack goes into a new array, which is pushed.
+    ///
+    /// This and the other opcodes that call back into the program fail with
+    /// [`VmError::StoppedInSubroutine`] if the subroutine halts or yields.
+    ArrayMap(usize),
+    /// Pops an array reference and calls the predicate subroutine at the
+    /// address once per element. Elements for which it leaves `true` go into
+    /// a new array, which is pushed.
+    ArrayFilter(usize),
+    /// Pops an initial accumulator and an array reference, and calls the
+    /// subroutine at the address once per element with the accumulator and
+    /// the element as its inputs. The value each call leaves on the stack is
+    /// the accumulator for the next, and the last one is pushed.
+    ArrayReduce(usize),
+
+    // Randomness
+    /// Pushes a pseudo-random non-negative integer from the VM's generator.
+    Rand,
+    /// Pops an upper and a lower bound and pushes a pseudo-random integer in
+    /// `lo..hi`.
+    RandRange,
+
+    // Time
+    /// Pushes the VM clock's reading in milliseconds, by default the time
+    /// since the VM was created. Readings beyond `i32::MAX` are an overflow.
+    Now,
+
+    // Introspection
+    HeapSize,
+    /// Pops a value and pushes whether it is a reference to an object that is
+    /// still on the heap. Popping a dangling reference is not an error here.
+    IsValid,
+    /// Pops a reference and pushes its reference count as it was before the
+    /// pop, so the inspected reference itself is included.
+    RefCount,
+    /// Pops two references and pushes whether they point at the same heap
+    /// object. Equal contents in different objects compare false.
+    RefEq,
+    /// Pops an actor or supervisor reference and pushes the instruction
+    /// pointer of its VM: its entry point until it is joined, and where it
+    /// stopped afterwards.
+    ActorIp,
+    /// Pushes the address of this `CurrentIp` instruction itself, not the
+    /// already-advanced instruction pointer.
+    CurrentIp,
+    /// Pushes a reference to a new string holding the crate version,
+    /// [`crate::VERSION`].
+    Version,
+
+    // Debugging
+    Assert(Option<Symbol>),
+    /// Fails unless the stack holds exactly this many values. Placed after a
+    /// `Call`, it checks that the subroutine kept the stack balanced.
+    ExpectDepth(usize),
+    /// Pops a value and writes it to the VM's output on its own line, as
+    /// rendered by [`Value::display`].
+    Print,
 }
 
 impl OpCode {
+    /// The opcode's name as written in source, without its operands.
+    pub fn name(&self) -> &'static str {
+        self.help().0
+    }
+
+    /// A one-line summary of what the opcode does, for help text.
+    pub fn describe(&self) -> &'static str {
+        self.help().1
+    }
+
+    fn help(&self) -> (&'static str, &'static str) {
+        match self {
+            OpCode::StoreVar(..) => ("StoreVar", "Pops a value into a local."),
+            OpCode::LoadVar(..) => ("LoadVar", "Pushes the value of a local."),
+            OpCode::ClearVar(..) => ("ClearVar", "Unsets a local, releasing its reference."),
+            OpCode::Tee(..) => ("Tee", "Stores the top value in a local without popping it."),
+            OpCode::UpdateVar(..) => (
+                "UpdateVar",
+                "Pops a value into a local that must already be set.",
+            ),
+            OpCode::Nop => ("Nop", "Does nothing."),
+            OpCode::PushConst(..) => ("PushConst", "Pushes a constant."),
+            OpCode::PushString(..) => ("PushString", "Pushes a new string."),
+            OpCode::PushBigInt(..) => ("PushBigInt", "Pushes a new big integer."),
+            OpCode::Pop => ("Pop", "Discards the top value."),
+            OpCode::Dup => ("Dup", "Duplicates the top value."),
+            OpCode::Swap => ("Swap", "Exchanges the top two values."),
+            OpCode::CMov => (
+                "CMov",
+                "Pops a condition and two values and keeps one of them.",
+            ),
+            OpCode::SwapN(..) => ("SwapN", "Exchanges the values at two depths."),
+            OpCode::Add => ("Add", "Pops two numbers and pushes their sum."),
+            OpCode::Sub => ("Sub", "Pops two numbers and pushes their difference."),
+            OpCode::Mul => ("Mul", "Pops two numbers and pushes their product."),
+            OpCode::Div => ("Div", "Pops two numbers and pushes their quotient."),
+            OpCode::SafeDiv => (
+                "SafeDiv",
+                "Divides like Div, pushing Null for a zero divisor.",
+            ),
+            OpCode::Mod => ("Mod", "Pops two integers and pushes the remainder."),
+            OpCode::Neg => ("Neg", "Negates the top number."),
+            OpCode::Exp => ("Exp", "Pops a base and an exponent and pushes the power."),
+            OpCode::Shl => ("Shl", "Shifts an integer left."),
+            OpCode::Shr => ("Shr", "Shifts an integer right arithmetically."),
+            OpCode::ToBool => ("ToBool", "Replaces the top value with its truthiness."),
+            OpCode::FloatEq => (
+                "FloatEq",
+                "Pops two numbers and a tolerance and pushes whether they are close.",
+            ),
+            OpCode::Jump(..) => ("Jump", "Jumps to an address."),
+            OpCode::JumpIfFalse(..) => ("JumpIfFalse", "Pops a boolean and jumps if it is false."),
+            OpCode::JumpIfTrue(..) => ("JumpIfTrue", "Pops a boolean and jumps if it is true."),
+            OpCode::JumpDyn => ("JumpDyn", "Pops an address and jumps to it."),
+            OpCode::JumpRel(..) => ("JumpRel", "Jumps by an offset from this instruction."),
+            OpCode::JumpIfFalseRel(..) => (
+                "JumpIfFalseRel",
+                "Pops a boolean and jumps by an offset if it is false.",
+            ),
+            OpCode::JumpIfTrueRel(..) => (
+                "JumpIfTrueRel",
+                "Pops a boolean and jumps by an offset if it is true.",
+            ),
+            OpCode::Call(..) => ("Call", "Calls the subroutine at an address."),
+            OpCode::TailCall(..) => (
+                "TailCall",
+                "Calls a subroutine in place of the current one.",
+            ),
+            OpCode::Return => ("Return", "Returns from the current subroutine."),
+            OpCode::Halt => ("Halt", "Ends the run."),
+            OpCode::Yield => ("Yield", "Ends the run so that it can be continued."),
+            OpCode::CallDepth => (
+                "CallDepth",
+                "Pushes the number of calls that have not returned.",
+            ),
+            OpCode::Switch(..) => (
+                "Switch",
+                "Pops an index and jumps through the table that follows.",
+            ),
+            OpCode::SpawnActor(..) => ("SpawnActor", "Spawns an actor starting at an address."),
+            OpCode::Fork(..) => ("Fork", "Spawns an actor with a copy of the current state."),
+            OpCode::SpawnDetached(..) => (
+                "SpawnDetached",
+                "Runs a copy of the current state from an address in the background.",
+            ),
+            OpCode::SendMessage => (
+                "SendMessage",
+                "Pops an actor and a message and sends the message.",
+            ),
+            OpCode::ReceiveMessage => (
+                "ReceiveMessage",
+                "Waits for the next message and pushes it.",
+            ),
+            OpCode::DrainMailbox => ("DrainMailbox", "Pushes an array of every queued message."),
+            OpCode::SendIfAlive => (
+                "SendIfAlive",
+                "Sends a message and pushes whether it was delivered.",
+            ),
+            OpCode::NewChannel => ("NewChannel", "Pushes a new channel."),
+            OpCode::ChannelSend => (
+                "ChannelSend",
+                "Pops a channel and a message and queues the message.",
+            ),
+            OpCode::ChannelRecv => (
+                "ChannelRecv",
+                "Pops a channel and pushes its oldest message.",
+            ),
+            OpCode::Select => (
+                "Select",
+                "Waits for a message on any of an array of channels.",
+            ),
+            OpCode::SendAll => ("SendAll", "Sends every element of an array to an actor."),
+            OpCode::JoinActor => (
+                "JoinActor",
+                "Runs an actor to completion and pushes its fault, if any.",
+            ),
+            OpCode::SpawnSupervisor(..) => (
+                "SpawnSupervisor",
+                "Spawns a supervisor starting at an address.",
+            ),
+            OpCode::SetStrategy(..) => ("SetStrategy", "Sets a supervisor's restart strategy."),
+            OpCode::SetStrategyDyn => (
+                "SetStrategyDyn",
+                "Pops a restart strategy and sets it on a supervisor.",
+            ),
+            OpCode::GetStrategy => ("GetStrategy", "Pushes a supervisor's restart strategy."),
+            OpCode::RestartChild(..) => ("RestartChild", "Restarts a supervised child."),
+            OpCode::NewBytes => ("NewBytes", "Pops a length and pushes a zeroed byte buffer."),
+            OpCode::BytesGet => (
+                "BytesGet",
+                "Pops a buffer and an index and pushes the byte.",
+            ),
+            OpCode::BytesLen => ("BytesLen", "Pops a buffer and pushes its length."),
+            OpCode::BytesSet => (
+                "BytesSet",
+                "Pops a buffer, an index, and a byte and stores the byte.",
+            ),
+            OpCode::Serialize => (
+                "Serialize",
+                "Pops a value and pushes bytes encoding it with its contents.",
+            ),
+            OpCode::Deserialize => (
+                "Deserialize",
+                "Pops bytes and pushes the value they encode.",
+            ),
+            OpCode::NewSet => ("NewSet", "Pushes a new set."),
+            OpCode::SetAdd => ("SetAdd", "Pops a set and a value and adds the value."),
+            OpCode::SetHas => (
+                "SetHas",
+                "Pops a set and a value and pushes whether it is a member.",
+            ),
+            OpCode::SetUnion => ("SetUnion", "Pops two sets and pushes their union."),
+            OpCode::SetIntersect => (
+                "SetIntersect",
+                "Pops two sets and pushes their intersection.",
+            ),
+            OpCode::NewCounter => ("NewCounter", "Pushes a new counter holding zero."),
+            OpCode::CounterInc => (
+                "CounterInc",
+                "Pops a counter, adds one to it atomically, and pushes the new count.",
+            ),
+            OpCode::CounterGet => ("CounterGet", "Pops a counter and pushes its count."),
+            OpCode::NewArray => ("NewArray", "Pushes a new array."),
+            OpCode::ArrayPush => (
+                "ArrayPush",
+                "Pops an array and a value and appends the value.",
+            ),
+            OpCode::Collect(..) => ("Collect", "Pops values into a new array."),
+            OpCode::Spread => ("Spread", "Pops an array and pushes its elements."),
+            OpCode::ArraySlice => (
+                "ArraySlice",
+                "Pops an array and a range and pushes that part of it.",
+            ),
+            OpCode::StrCodePoints => (
+                "StrCodePoints",
+                "Pops a string and pushes an array of its code points.",
+            ),
+            OpCode::Unbox => (
+                "Unbox",
+                "Pops a one-element array or one-character string and pushes its content.",
+            ),
+            OpCode::ArrayToStr => (
+                "ArrayToStr",
+                "Pops an array of code points and pushes a string.",
+            ),
+            OpCode::ArrayMap(..) => (
+                "ArrayMap",
+                "Pushes an array of a subroutine's results for each element.",
+            ),
+            OpCode::ArrayFilter(..) => ("ArrayFilter", "Pushes the elements a subroutine accepts."),
+            OpCode::ArrayReduce(..) => (
+                "ArrayReduce",
+                "Folds an array into an accumulator with a subroutine.",
+            ),
+            OpCode::Rand => ("Rand", "Pushes a random non-negative integer."),
+            OpCode::RandRange => (
+                "RandRange",
+                "Pops bounds and pushes a random integer between them.",
+            ),
+            OpCode::Now => ("Now", "Pushes the clock's time in milliseconds."),
+            OpCode::HeapSize => ("HeapSize", "Pushes the number of heap objects."),
+            OpCode::IsValid => (
+                "IsValid",
+                "Pops a reference and pushes whether it is valid.",
+            ),
+            OpCode::RefCount => (
+                "RefCount",
+                "Pops a reference and pushes its reference count.",
+            ),
+            OpCode::RefEq => (
+                "RefEq",
+                "Pops two references and pushes whether they are the same object.",
+            ),
+            OpCode::ActorIp => (
+                "ActorIp",
+                "Pops an actor and pushes its instruction pointer.",
+            ),
+            OpCode::CurrentIp => ("CurrentIp", "Pushes this instruction's address."),
+            OpCode::Version => ("Version", "Pushes the runtime's version string."),
+            OpCode::Assert(..) => ("Assert", "Pops a boolean and fails unless it is true."),
+            OpCode::ExpectDepth(..) => (
+                "ExpectDepth",
+                "Fails unless the stack holds exactly this many values.",
+            ),
+            OpCode::Print => ("Print", "Pops a value and prints it."),
+        }
+    }
+
+    /// The bytecode address operand of opcodes that transfer control, used
+    /// when resolving labels and relocating linked code.
+    pub fn target_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            OpCode::Jump(target)
+            | OpCode::JumpIfFalse(target)
+            | OpCode::JumpIfTrue(target)
+            | OpCode::Call(target)
+            | OpCode::TailCall(target)
+            | OpCode::SpawnActor(target)
+            | OpCode::Fork(target)
+            | OpCode::SpawnDetached(target)
+            | OpCode::SpawnSupervisor(target)
+            | OpCode::ArrayMap(target)
+            | OpCode::ArrayFilter(target)
+            | OpCode::ArrayReduce(target) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Whether executing this opcode may wait on a channel or another actor,
+    /// or runs a subroutine that might. Every other opcode
+    /// completes without yielding and can run through
+    /// [`OpCode::execute_sync`].
+    pub fn is_async(&self) -> bool {
+        matches!(
+            self,
+            OpCode::ReceiveMessage
+                | OpCode::DrainMailbox
+                | OpCode::SendMessage
+                | OpCode::SendIfAlive
+                | OpCode::JoinActor
+                | OpCode::Select
+                | OpCode::ArrayMap(_)
+                | OpCode::ArrayFilter(_)
+                | OpCode::ArrayReduce(_)
+        )
+    }
+
     pub async fn execute(
         &self,
         execution: &mut ExecutionContext,
@@ -123,16 +958,319 @@ impl OpCode {
         mailbox: &mut Receiver<Value>,
     ) -> Result<(), VmError> {
         match self {
-            OpCode::Add => binary_op(&mut execution.stack, |a, b| a.add(b)),
-            OpCode::Sub => binary_op(&mut execution.stack, |a, b| a.sub(b)),
-            OpCode::Mul => binary_op(&mut execution.stack, |a, b| a.mul(b)),
+            OpCode::ReceiveMessage => {
+                if let Some(message) = mailbox.recv().await {
+                    log::info!("Received message: {:?}", message);
+                    execution.messages_received += 1;
+                    if let Some(MessageHook(hook)) = &mut execution.on_message {
+                        hook(&message);
+                    }
+                    if let Some(budget) = &mut execution.message_budget {
+                        *budget = budget.saturating_sub(1);
+                        if *budget == 0 {
+                            execution.stop = Some(RunResult::Yielded);
+                        }
+                    }
+                    // The count the sender gave the queued message is handed
+                    // to the stack as is.
+                    if let Value::Reference(address) = message {
+                        if heap.get(address).is_none() {
+                            return Err(VmError::InvalidReference);
+                        }
+                    }
+                    push_owned(execution, message)
+                } else {
+                    log::warn!("Mailbox is closed");
+                    Err(VmError::MailboxClosed)
+                }
+            }
+            OpCode::DrainMailbox => {
+                check_stack_capacity(execution)?;
+                let mut messages = Vec::new();
+                while let Ok(message) = mailbox.try_recv() {
+                    execution.messages_received += 1;
+                    if let Some(MessageHook(hook)) = &mut execution.on_message {
+                        hook(&message);
+                    }
+                    // The array takes over the count each queued reference
+                    // carries.
+                    if let Value::Reference(address) = message {
+                        if heap.get(address).is_none() {
+                            // Nothing will own the messages drained so far.
+                            for message in messages {
+                                if let Value::Reference(address) = message {
+                                    decrement_reference(heap, address)?;
+                                }
+                            }
+                            return Err(VmError::InvalidReference);
+                        }
+                    }
+                    messages.push(message);
+                }
+                log::info!("Drained {} messages", messages.len());
+                let address = heap.allocate(HeapObject::Array(messages, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::SendMessage => {
+                let actor_ref = pop_value(execution, heap)?;
+                let message = pop_value(execution, heap)?;
+                if let Value::Reference(address) = actor_ref {
+                    let (sender, channel) = match heap.get(address) {
+                        Some(HeapObject::Actor(_, sender, _)) => (sender.clone(), false),
+                        Some(HeapObject::Channel(sender, _, _)) => (sender.clone(), true),
+                        _ => return Err(VmError::InvalidReference),
+                    };
+                    if let Value::Reference(message_address) = message {
+                        increment_reference(heap, message_address)?;
+                    }
+                    if channel {
+                        // This VM holds the channel's receiver, so waiting for
+                        // room would wait on itself; fail as `ChannelSend` does.
+                        if sender.try_send(message).is_err() {
+                            if let Value::Reference(message_address) = message {
+                                decrement_reference(heap, message_address)?;
+                            }
+                            return Err(VmError::MailboxFull(1));
+                        }
+                        return push_value(execution, heap, Value::Reference(address));
+                    }
+                    match sender.send(message).await {
+                        Ok(()) => push_value(execution, heap, Value::Reference(address)),
+                        // Keep the recovered message alive so that callers can
+                        // safely inspect or resend it from the returned error.
+                        // The send attempt already incremented the reference
+                        // count to transfer ownership to the channel, so we
+                        // intentionally skip the corresponding decrement here.
+                        Err(err) => Err(err.into()),
+                    }
+                } else {
+                    Err(VmError::InvalidReference)
+                }
+            }
+            OpCode::SendIfAlive => {
+                // A removed actor leaves a stale reference behind, which
+                // counts as dead rather than as an invalid reference.
+                let actor_ref = execution.stack.pop().ok_or(VmError::StackUnderflow)?;
+                if let Value::Reference(address) = actor_ref {
+                    if let Some(object) = heap.get_mut(address) {
+                        object.decrement_ref();
+                    }
+                }
+                let message = pop_value(execution, heap)?;
+                let Value::Reference(address) = actor_ref else {
+                    return Err(VmError::TypeMismatch("SendIfAlive"));
+                };
+                let (sender, channel) = match heap.get(address) {
+                    Some(HeapObject::Actor(_, sender, _)) if !sender.is_closed() => {
+                        (sender.clone(), false)
+                    }
+                    Some(HeapObject::Channel(sender, _, _)) => (sender.clone(), true),
+                    _ => {
+                        log::info!("SendIfAlive target {} is gone", address);
+                        return push_value(execution, heap, Value::Boolean(false));
+                    }
+                };
+                if let Value::Reference(message_address) = message {
+                    increment_reference(heap, message_address)?;
+                }
+                // A full channel counts as undelivered rather than waiting,
+                // since only this VM could make room.
+                let delivered = if channel {
+                    sender.try_send(message).is_ok()
+                } else {
+                    sender.send(message).await.is_ok()
+                };
+                if !delivered {
+                    // Nothing was queued, so take back the count.
+                    if let Value::Reference(message_address) = message {
+                        decrement_reference(heap, message_address)?;
+                    }
+                }
+                push_value(execution, heap, Value::Boolean(delivered))
+            }
+            OpCode::Select => {
+                let channels_ref = pop_value(execution, heap)?;
+                let channels = array_contents(heap, channels_ref)?
+                    .iter()
+                    .map(|channel| match channel {
+                        Value::Reference(address)
+                            if matches!(heap.get(*address), Some(HeapObject::Channel(..))) =>
+                        {
+                            Ok(*address)
+                        }
+                        _ => Err(VmError::InvalidReference),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (index, message) = poll_fn(|cx| {
+                    let mut open = false;
+                    for (index, address) in channels.iter().enumerate() {
+                        let Some(HeapObject::Channel(_, receiver, _)) = heap.get_mut(*address)
+                        else {
+                            continue;
+                        };
+                        match receiver.poll_recv(cx) {
+                            Poll::Ready(Some(message)) => {
+                                return Poll::Ready(Some((index, message)))
+                            }
+                            Poll::Ready(None) => {}
+                            Poll::Pending => open = true,
+                        }
+                    }
+                    if open {
+                        Poll::Pending
+                    } else {
+                        Poll::Ready(None)
+                    }
+                })
+                .await
+                .ok_or(VmError::MailboxClosed)?;
+                log::info!("Selected message {:?} from channel {}", message, index);
+                // As with `ReceiveMessage`, the queued count moves to the stack.
+                push_owned(execution, message)?;
+                push_value(execution, heap, Value::Integer(index as i32))
+            }
+            OpCode::JoinActor => {
+                let Value::Reference(address) = pop_value(execution, heap)? else {
+                    return Err(VmError::TypeMismatch("JoinActor"));
+                };
+                // Spawned actors only run when joined, so any messages they
+                // expect must already be in their mailbox.
+                let result = match heap.get_mut(address) {
+                    Some(HeapObject::Actor(vm, _, _)) => Box::pin(vm.run()).await,
+                    _ => return Err(VmError::InvalidReference),
+                };
+                match result {
+                    Ok(_) => push_value(execution, heap, Value::Null),
+                    Err(err) => {
+                        log::warn!("Joined actor at {} failed: {}", address, err);
+                        let fault = heap.allocate(HeapObject::String(err.to_string(), 0));
+                        push_value(execution, heap, Value::Reference(fault))
+                    }
+                }
+            }
+            OpCode::ArrayMap(addr) => {
+                let array_ref = pop_value(execution, heap)?;
+                let items = array_contents(heap, array_ref)?.clone();
+                let mut mapped = Vec::with_capacity(items.len());
+                for item in items {
+                    let value = call_subroutine(execution, heap, mailbox, *addr, &[item]).await?;
+                    // The new array takes over ownership of a returned reference.
+                    if let Value::Reference(address) = value {
+                        increment_reference(heap, address)?;
+                    }
+                    mapped.push(value);
+                }
+                let address = heap.allocate(HeapObject::Array(mapped, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::ArrayFilter(addr) => {
+                let array_ref = pop_value(execution, heap)?;
+                let items = array_contents(heap, array_ref)?.clone();
+                let mut retained = Vec::new();
+                for item in items {
+                    match call_subroutine(execution, heap, mailbox, *addr, &[item]).await? {
+                        Value::Boolean(true) => {
+                            // Only retained elements gain the new array as an owner.
+                            if let Value::Reference(address) = item {
+                                increment_reference(heap, address)?;
+                            }
+                            retained.push(item);
+                        }
+                        Value::Boolean(false) => {}
+                        _ => return Err(VmError::TypeMismatch("ArrayFilter")),
+                    }
+                }
+                let address = heap.allocate(HeapObject::Array(retained, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::ArrayReduce(addr) => {
+                let mut accumulator = pop_value(execution, heap)?;
+                let array_ref = pop_value(execution, heap)?;
+                let items = array_contents(heap, array_ref)?.clone();
+                for item in items {
+                    accumulator =
+                        call_subroutine(execution, heap, mailbox, *addr, &[accumulator, item])
+                            .await?;
+                }
+                push_value(execution, heap, accumulator)
+            }
+            _ => self.execute_sync(execution, heap),
+        }
+    }
+
+    /// Executes an opcode that never waits. Async opcodes (see
+    /// [`OpCode::is_async`]) are rejected and must go through
+    /// [`OpCode::execute`].
+    pub fn execute_sync(
+        &self,
+        execution: &mut ExecutionContext,
+        heap: &mut Heap,
+    ) -> Result<(), VmError> {
+        match self {
+            OpCode::ReceiveMessage
+            | OpCode::DrainMailbox
+            | OpCode::SendMessage
+            | OpCode::SendIfAlive
+            | OpCode::JoinActor
+            | OpCode::Select
+            | OpCode::ArrayMap(_)
+            | OpCode::ArrayFilter(_)
+            | OpCode::ArrayReduce(_) => Err(VmError::Message(format!(
+                "{:?} cannot be executed synchronously",
+                self
+            ))),
+            OpCode::Add => big_binary_op(execution, heap, "Add", Value::add, |a, b| a + b),
+            OpCode::Sub => big_binary_op(execution, heap, "Sub", Value::sub, |a, b| a - b),
+            OpCode::Mul => big_binary_op(execution, heap, "Mul", Value::mul, |a, b| a * b),
             OpCode::Div => binary_op(&mut execution.stack, |a, b| a.div(b)),
+            OpCode::SafeDiv => binary_op(&mut execution.stack, |a, b| match a.div(b) {
+                Err(VmError::DivisionByZero) => Ok(Value::Null),
+                Err(VmError::TypeMismatch(_)) => Err(VmError::TypeMismatch("SafeDiv")),
+                result => result,
+            }),
             OpCode::Neg => unary_op(&mut execution.stack, |a| match a {
                 Value::Integer(i) => Ok(Value::Integer(-i)),
                 Value::Float(f) => Ok(Value::Float(-f)),
                 _ => Err(VmError::TypeMismatch("Neg")),
             }),
+            OpCode::FloatEq => {
+                if execution.stack.len() < 3 {
+                    return Err(VmError::StackUnderflowFor("FloatEq"));
+                }
+                let mut operands = [0.0; 3];
+                for operand in operands.iter_mut().rev() {
+                    *operand = match pop_value(execution, heap)? {
+                        Value::Float(f) => f,
+                        Value::Integer(i) => f64::from(i),
+                        _ => return Err(VmError::TypeMismatch("FloatEq")),
+                    };
+                }
+                let [a, b, epsilon] = operands;
+                push_value(execution, heap, Value::Boolean((a - b).abs() < epsilon))
+            }
+            OpCode::ToBool => {
+                let truthy = match pop_value(execution, heap)? {
+                    Value::Boolean(b) => b,
+                    Value::Integer(i) => i != 0,
+                    Value::Float(f) => f != 0.0,
+                    Value::Reference(_) => true,
+                    Value::Null => false,
+                };
+                push_value(execution, heap, Value::Boolean(truthy))
+            }
             OpCode::PushConst(v) => push_value(execution, heap, *v),
+            OpCode::PushString(text) => {
+                let address = heap.allocate(HeapObject::String(text.as_str().to_string(), 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::PushBigInt(digits) => {
+                let value = digits
+                    .as_str()
+                    .parse::<BigInt>()
+                    .map_err(|_| VmError::InvalidEncoding(format!("big integer {}", digits)))?;
+                push_big(execution, heap, value)
+            }
+            OpCode::Nop => Ok(()),
             OpCode::Pop => {
                 pop_value(execution, heap)?;
                 Ok(())
@@ -152,13 +1290,31 @@ impl OpCode {
                 execution.stack.swap(len - 1, len - 2);
                 Ok(())
             }
+            OpCode::CMov => {
+                if execution.stack.len() < 3 {
+                    return Err(VmError::StackUnderflowFor("CMov"));
+                }
+                let Value::Boolean(condition) = pop_value(execution, heap)? else {
+                    return Err(VmError::TypeMismatch("CMov"));
+                };
+                let otherwise = pop_value(execution, heap)?;
+                let then = pop_value(execution, heap)?;
+                push_value(execution, heap, if condition { then } else { otherwise })
+            }
+            OpCode::SwapN(a, b) => {
+                let len = execution.stack.len();
+                if *a >= len || *b >= len {
+                    log::error!("SwapN({}, {}) on stack of depth {}", a, b, len);
+                    return Err(VmError::StackUnderflow);
+                }
+                execution.stack.swap(len - 1 - a, len - 1 - b);
+                Ok(())
+            }
             OpCode::StoreVar(index) => {
                 let value = pop_value(execution, heap)?;
 
-                if let Some(existing) = execution.locals.insert(*index, value) {
-                    if let Value::Reference(address) = existing {
-                        decrement_reference(heap, address)?;
-                    }
+                if let Some(Value::Reference(address)) = execution.locals.insert(*index, value) {
+                    decrement_reference(heap, address)?;
                 }
 
                 if let Value::Reference(address) = value {
@@ -167,9 +1323,43 @@ impl OpCode {
 
                 Ok(())
             }
+            OpCode::Tee(index) => {
+                let value = *execution
+                    .stack
+                    .last()
+                    .ok_or(VmError::StackUnderflowFor("Tee"))?;
+                if let Value::Reference(address) = value {
+                    increment_reference(heap, address)?;
+                }
+                if let Some(Value::Reference(address)) = execution.locals.insert(*index, value) {
+                    decrement_reference(heap, address)?;
+                }
+                Ok(())
+            }
+            OpCode::UpdateVar(index) => {
+                if !execution.locals.contains_key(index) {
+                    return Err(VmError::VariableNotFound(*index));
+                }
+                let value = pop_value(execution, heap)?;
+                if let Value::Reference(address) = value {
+                    increment_reference(heap, address)?;
+                }
+                if let Some(Value::Reference(address)) = execution.locals.insert(*index, value) {
+                    decrement_reference(heap, address)?;
+                }
+                Ok(())
+            }
+            OpCode::ClearVar(index) => {
+                if let Some(Value::Reference(address)) = execution.locals.remove(index) {
+                    decrement_reference(heap, address)?;
+                }
+                Ok(())
+            }
             OpCode::LoadVar(index) => {
                 if let Some(value) = execution.locals.get(index) {
                     push_value(execution, heap, *value)
+                } else if execution.default_locals_to_null {
+                    push_value(execution, heap, Value::Null)
                 } else {
                     Err(VmError::VariableNotFound(*index))
                 }
@@ -186,62 +1376,119 @@ impl OpCode {
             }),
             OpCode::Exp => binary_op(&mut execution.stack, |a, b| match (a, b) {
                 (Value::Integer(x), Value::Integer(y)) => {
+                    // A negative exponent yields a fraction, so it is
+                    // computed as a float. Any non-negative `i32` fits in `u32`.
                     if y < 0 {
                         Ok(Value::Float((x as f64).powi(y)))
                     } else {
-                        Ok(Value::Integer(x.pow(y as u32)))
+                        x.checked_pow(y as u32)
+                            .map(Value::Integer)
+                            .ok_or(VmError::IntegerOverflow("Exp"))
                     }
                 }
                 (Value::Float(x), Value::Float(y)) => Ok(Value::Float(x.powf(y))),
                 _ => Err(VmError::TypeMismatch("Exp")),
             }),
+            OpCode::Shl => binary_op(&mut execution.stack, |a, b| {
+                let (value, count) = shift_operands(a, b, "Shl")?;
+                Ok(Value::Integer(value.checked_shl(count).unwrap_or(0)))
+            }),
+            OpCode::Shr => binary_op(&mut execution.stack, |a, b| {
+                let (value, count) = shift_operands(a, b, "Shr")?;
+                Ok(Value::Integer(value >> count.min(31)))
+            }),
             OpCode::Jump(target) => {
-                if *target > execution.bytecode.len() {
-                    log::error!(
-                        "Jump target {} out of bounds (bytecode length {})",
-                        target,
-                        execution.bytecode.len()
-                    );
-                    return Err(VmError::ExecutionOutOfBounds);
-                }
-
+                check_target(execution, *target, "Jump")?;
                 execution.ip = *target;
                 Ok(())
             }
 
-            OpCode::JumpIfFalse(target) => {
-                let value = pop_value(execution, heap)?;
-                match value {
-                    Value::Boolean(false) => {
-                        if *target > execution.bytecode.len() {
-                            log::error!(
-                                "JumpIfFalse target {} out of bounds (bytecode length {})",
-                                target,
-                                execution.bytecode.len()
-                            );
-                            return Err(VmError::ExecutionOutOfBounds);
-                        }
-                        execution.ip = *target;
+            OpCode::JumpIfFalse(target) => jump_if(execution, heap, *target, false, "JumpIfFalse"),
+            OpCode::JumpIfTrue(target) => jump_if(execution, heap, *target, true, "JumpIfTrue"),
+            OpCode::JumpDyn => {
+                let Value::Integer(target) = pop_value(execution, heap)? else {
+                    return Err(VmError::TypeMismatch("JumpDyn"));
+                };
+                match usize::try_from(target) {
+                    Ok(target) if target <= execution.bytecode.len() => {
+                        execution.ip = target;
                         Ok(())
                     }
-                    Value::Boolean(true) => Ok(()),
-                    _ => Err(VmError::TypeMismatch("JumpIfFalse")),
+                    _ => {
+                        log::error!(
+                            "JumpDyn target {} out of bounds (bytecode length {})",
+                            target,
+                            execution.bytecode.len()
+                        );
+                        Err(VmError::ExecutionOutOfBounds)
+                    }
                 }
             }
-            OpCode::Call(addr) => {
-                if *addr >= execution.bytecode.len() {
+            OpCode::JumpRel(offset) => {
+                let target = relative_target(execution, *offset);
+                if target > execution.bytecode.len() {
                     log::error!(
-                        "Call target {} out of bounds (bytecode length {})",
-                        addr,
+                        "JumpRel offset {} out of bounds (bytecode length {})",
+                        offset,
                         execution.bytecode.len()
                     );
                     return Err(VmError::ExecutionOutOfBounds);
                 }
-
+                execution.ip = target;
+                Ok(())
+            }
+            OpCode::JumpIfFalseRel(offset) => {
+                let target = relative_target(execution, *offset);
+                jump_if(execution, heap, target, false, "JumpIfFalseRel")
+            }
+            OpCode::JumpIfTrueRel(offset) => {
+                let target = relative_target(execution, *offset);
+                jump_if(execution, heap, target, true, "JumpIfTrueRel")
+            }
+            OpCode::Call(addr) => {
+                check_target(execution, *addr, "Call")?;
                 execution.call_stack.push(execution.ip);
                 execution.ip = *addr;
                 Ok(())
             }
+            OpCode::TailCall(addr) => {
+                check_target(execution, *addr, "TailCall")?;
+                // Reuse the caller's frame: the callee returns straight to
+                // whoever called the current function.
+                execution.ip = *addr;
+                Ok(())
+            }
+            OpCode::Switch(count) => {
+                let table = execution.ip;
+                let len = execution.bytecode.len();
+                let Some(end) = table.checked_add(*count).filter(|end| *end <= len) else {
+                    log::error!(
+                        "Switch table at {} with {} entries exceeds bytecode length {}",
+                        table,
+                        count,
+                        len
+                    );
+                    return Err(VmError::ExecutionOutOfBounds);
+                };
+                let index = match pop_value(execution, heap)? {
+                    Value::Integer(index) => index,
+                    _ => return Err(VmError::TypeMismatch("Switch")),
+                };
+                match usize::try_from(index).ok().filter(|index| index < count) {
+                    Some(index) => match execution.bytecode[table + index] {
+                        OpCode::Jump(target) if target <= len => {
+                            execution.ip = target;
+                            Ok(())
+                        }
+                        OpCode::Jump(_) => Err(VmError::ExecutionOutOfBounds),
+                        _ => Err(VmError::MalformedSwitchTable(table + index)),
+                    },
+                    None => {
+                        execution.ip = end;
+                        Ok(())
+                    }
+                }
+            }
             OpCode::Return => {
                 if let Some(return_addr) = execution.call_stack.pop() {
                     execution.ip = return_addr;
@@ -250,85 +1497,87 @@ impl OpCode {
                     Err(VmError::StackUnderflow)
                 }
             }
-            OpCode::ReceiveMessage => {
-                if let Some(message) = mailbox.recv().await {
-                    log::info!("Received message: {:?}", message);
-                    if let Value::Reference(address) = message {
-                        decrement_reference(heap, address)?;
-                    }
-                    push_value(execution, heap, message)
-                } else {
-                    log::warn!("Mailbox is empty or closed");
-                    Err(VmError::MailboxEmpty)
-                }
+            OpCode::Halt => {
+                execution.stop = Some(RunResult::Halted);
+                Ok(())
+            }
+            OpCode::Yield => {
+                execution.stop = Some(RunResult::Yielded);
+                Ok(())
+            }
+            OpCode::CallDepth => {
+                let depth = execution.call_stack.len();
+                push_value(execution, heap, Value::Integer(depth as i32))
             }
-
             OpCode::SpawnActor(addr) => {
-                let bytecode = execution.bytecode.clone();
-                let (mut vm, tx) = VM::new(bytecode, None);
-                if *addr >= execution.bytecode.len() {
-                    log::error!(
-                        "SpawnActor target {} out of bounds (bytecode length {})",
-                        addr,
-                        execution.bytecode.len()
-                    );
-                    return Err(VmError::ExecutionOutOfBounds);
-                }
-                vm.set_ip(*addr);
+                check_target(execution, *addr, "SpawnActor")?;
+                check_actor_limit(execution, heap)?;
+                let (mut vm, tx) = child_vm(execution);
+                vm.set_entry(*addr);
                 let address = heap.allocate(HeapObject::Actor(vm, tx, 0));
                 push_value(execution, heap, Value::Reference(address))
             }
-            OpCode::SendMessage => {
-                let actor_ref = pop_value(execution, heap)?;
-                let message = pop_value(execution, heap)?;
-                if let Value::Reference(address) = actor_ref {
-                    let sender = match heap.get(address) {
-                        Some(HeapObject::Actor(_actor_vm, sender, _)) => sender.clone(),
-                        _ => return Err(VmError::InvalidReference),
-                    };
-                    if let Value::Reference(message_address) = message {
-                        increment_reference(heap, message_address)?;
-                    }
-                    match sender.send(message).await {
-                        Ok(()) => push_value(execution, heap, Value::Reference(address)),
-                        Err(err) => {
-                            let error = err.to_string();
-                            let failed_message = err.0;
-                            // Keep the recovered message alive so that callers can
-                            // safely inspect or resend it from the returned error.
-                            // The send attempt already incremented the reference
-                            // count to transfer ownership to the channel, so we
-                            // intentionally skip the corresponding decrement here.
-                            Err(VmError::ChannelSend {
-                                error,
-                                value: failed_message,
-                            })
-                        }
+            OpCode::Fork(addr) => {
+                check_target(execution, *addr, "Fork")?;
+                check_actor_limit(execution, heap)?;
+                let (mut vm, tx) = child_vm(execution);
+                vm.restore(&VmSnapshot::capture(execution, heap));
+                vm.set_entry(*addr);
+                let address = heap.allocate(HeapObject::Actor(vm, tx, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::SpawnDetached(addr) => {
+                check_target(execution, *addr, "SpawnDetached")?;
+                check_actor_limit(execution, heap)?;
+                let runtime = tokio::runtime::Handle::try_current().map_err(|_| {
+                    VmError::Message("SpawnDetached needs a tokio runtime".to_string())
+                })?;
+                let (mut vm, tx) = child_vm(execution);
+                vm.restore(&VmSnapshot::capture(execution, heap));
+                vm.set_entry(*addr);
+                runtime.spawn(async move {
+                    // Holding the sender keeps the mailbox open while it runs.
+                    let _tx = tx;
+                    if let Err(e) = vm.run().await {
+                        log::error!("Detached actor failed: {}", e);
                     }
-                } else {
-                    Err(VmError::InvalidReference)
-                }
+                });
+                Ok(())
             }
             OpCode::SpawnSupervisor(addr) => {
-                let bytecode = execution.bytecode.clone();
-                let (mut vm, tx) = VM::new(bytecode, None);
-                if *addr >= execution.bytecode.len() {
-                    log::error!(
-                        "SpawnSupervisor target {} out of bounds (bytecode length {})",
-                        addr,
-                        execution.bytecode.len()
-                    );
-                    return Err(VmError::ExecutionOutOfBounds);
-                }
-                vm.set_ip(*addr);
+                check_target(execution, *addr, "SpawnSupervisor")?;
+                check_actor_limit(execution, heap)?;
+                let (mut vm, tx) = child_vm(execution);
+                vm.set_entry(*addr);
                 let address = heap.allocate(HeapObject::Supervisor(vm, tx, 0));
                 push_value(execution, heap, Value::Reference(address))
             }
-            OpCode::SetStrategy(strategy) => {
+            OpCode::SetStrategy(strategy) => set_strategy(execution, heap, *strategy),
+            OpCode::SetStrategyDyn => {
+                let strategy = match pop_value(execution, heap)? {
+                    Value::Integer(strategy) => usize::try_from(strategy)
+                        .map_err(|_| VmError::TypeMismatch("SetStrategyDyn"))?,
+                    _ => return Err(VmError::TypeMismatch("SetStrategyDyn")),
+                };
+                set_strategy(execution, heap, strategy)
+            }
+            OpCode::GetStrategy => {
+                let strategy = match execution.stack.last() {
+                    Some(Value::Reference(address)) => match heap.get(*address) {
+                        Some(HeapObject::Supervisor(vm, _, _)) => vm.strategy(),
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    Some(_) => return Err(VmError::TypeMismatch("GetStrategy")),
+                    None => return Err(VmError::StackUnderflowFor("GetStrategy")),
+                };
+                pop_value(execution, heap)?;
+                push_value(execution, heap, Value::Integer(strategy as i32))
+            }
+            OpCode::RestartChild(child) => {
                 let sup_ref = pop_value(execution, heap)?;
                 if let Value::Reference(addr) = sup_ref {
                     if let Some(HeapObject::Supervisor(vm, _, _)) = heap.get_mut(addr) {
-                        vm.set_strategy(*strategy);
+                        vm.restart_child(*child);
                     } else {
                         return Err(VmError::InvalidReference);
                     }
@@ -337,19 +1586,560 @@ impl OpCode {
                     Err(VmError::InvalidReference)
                 }
             }
-            OpCode::RestartChild(child) => {
-                let sup_ref = pop_value(execution, heap)?;
-                if let Value::Reference(addr) = sup_ref {
-                    if let Some(HeapObject::Supervisor(vm, _, _)) = heap.get_mut(addr) {
-                        vm.restart_child(*child);
-                    } else {
+            OpCode::NewBytes => {
+                let len = match pop_value(execution, heap)? {
+                    Value::Integer(len) => {
+                        usize::try_from(len).map_err(|_| VmError::IndexOutOfBounds)?
+                    }
+                    _ => return Err(VmError::TypeMismatch("NewBytes")),
+                };
+                let address = heap.allocate(HeapObject::Bytes(vec![0; len], 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::BytesGet => {
+                let index = pop_value(execution, heap)?;
+                let bytes_ref = pop_value(execution, heap)?;
+                let bytes = bytes_mut(heap, bytes_ref)?;
+                let byte = bytes[index_operand(index, bytes.len(), "BytesGet")?];
+                push_value(execution, heap, Value::Integer(byte as i32))
+            }
+            OpCode::BytesLen => {
+                let bytes_ref = pop_value(execution, heap)?;
+                let len = bytes_mut(heap, bytes_ref)?.len();
+                push_value(execution, heap, Value::Integer(len as i32))
+            }
+            OpCode::BytesSet => {
+                let value = pop_value(execution, heap)?;
+                let index = pop_value(execution, heap)?;
+                let bytes_ref = pop_value(execution, heap)?;
+                let byte = match value {
+                    Value::Integer(value) => {
+                        u8::try_from(value).map_err(|_| VmError::ByteOutOfRange(value))?
+                    }
+                    _ => return Err(VmError::TypeMismatch("BytesSet")),
+                };
+                let bytes = bytes_mut(heap, bytes_ref)?;
+                let index = index_operand(index, bytes.len(), "BytesSet")?;
+                bytes[index] = byte;
+                push_value(execution, heap, bytes_ref)
+            }
+            OpCode::Serialize => {
+                let value = pop_value(execution, heap)?;
+                let bytes = value.serialize(heap)?;
+                let address = heap.allocate(HeapObject::Bytes(bytes, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::Deserialize => {
+                let bytes_ref = pop_value(execution, heap)?;
+                let bytes = bytes_mut(heap, bytes_ref)?.clone();
+                let value = Value::deserialize(&bytes, heap)?;
+                push_value(execution, heap, value)
+            }
+            OpCode::NewSet => {
+                let address = heap.allocate(HeapObject::Set(HashSet::new(), 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::SetAdd => {
+                let value = pop_value(execution, heap)?;
+                let set_ref = pop_value(execution, heap)?;
+                if !value.is_hashable() {
+                    return Err(VmError::TypeMismatch("SetAdd"));
+                }
+                let inserted = match set_ref {
+                    Value::Reference(address) => match heap.get_mut(address) {
+                        Some(HeapObject::Set(set, _)) => set.insert(value),
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    _ => return Err(VmError::InvalidReference),
+                };
+                // The set becomes an owner of newly inserted references.
+                if inserted {
+                    if let Value::Reference(address) = value {
+                        increment_reference(heap, address)?;
+                    }
+                }
+                push_value(execution, heap, set_ref)
+            }
+            OpCode::SetHas => {
+                let value = pop_value(execution, heap)?;
+                let set_ref = pop_value(execution, heap)?;
+                let found = set_contents(heap, set_ref)?.contains(&value);
+                push_value(execution, heap, Value::Boolean(found))
+            }
+            OpCode::SetUnion => {
+                set_operation(execution, heap, |a, b| a.union(b).copied().collect())
+            }
+            OpCode::SetIntersect => {
+                set_operation(execution, heap, |a, b| a.intersection(b).copied().collect())
+            }
+            OpCode::NewCounter => {
+                let counter = HeapObject::Counter(Arc::new(AtomicI64::new(0)), 0);
+                let address = heap.allocate(counter);
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::CounterInc => {
+                let counter_ref = pop_value(execution, heap)?;
+                let count = counter(heap, counter_ref)?.fetch_add(1, Ordering::SeqCst) + 1;
+                let count =
+                    i32::try_from(count).map_err(|_| VmError::IntegerOverflow("CounterInc"))?;
+                push_value(execution, heap, Value::Integer(count))
+            }
+            OpCode::CounterGet => {
+                let counter_ref = pop_value(execution, heap)?;
+                let count = counter(heap, counter_ref)?.load(Ordering::SeqCst);
+                let count =
+                    i32::try_from(count).map_err(|_| VmError::IntegerOverflow("CounterGet"))?;
+                push_value(execution, heap, Value::Integer(count))
+            }
+            OpCode::NewArray => {
+                let address = heap.allocate(HeapObject::Array(Vec::new(), 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::ArrayPush => {
+                let value = pop_value(execution, heap)?;
+                let array_ref = pop_value(execution, heap)?;
+                match array_ref {
+                    Value::Reference(address) => match heap.get_mut(address) {
+                        Some(HeapObject::Array(items, _)) => items.push(value),
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    _ => return Err(VmError::InvalidReference),
+                }
+                // The array becomes an owner of a pushed reference.
+                if let Value::Reference(address) = value {
+                    increment_reference(heap, address)?;
+                }
+                push_value(execution, heap, array_ref)
+            }
+            OpCode::Collect(count) => {
+                let Some(start) = execution.stack.len().checked_sub(*count) else {
+                    log::error!("Stack underflow collecting {} values", count);
+                    return Err(VmError::StackUnderflow);
+                };
+                // References move from the stack to the array with their counts.
+                let items = execution.stack.split_off(start);
+                let address = heap.allocate(HeapObject::Array(items, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::Spread => {
+                let array_ref = pop_value(execution, heap)?;
+                let items = array_contents(heap, array_ref)?.clone();
+                for item in items {
+                    push_value(execution, heap, item)?;
+                }
+                Ok(())
+            }
+            OpCode::ArraySlice => {
+                let end = pop_value(execution, heap)?;
+                let start = pop_value(execution, heap)?;
+                let array_ref = pop_value(execution, heap)?;
+                let (Value::Integer(start), Value::Integer(end)) = (start, end) else {
+                    return Err(VmError::TypeMismatch("ArraySlice"));
+                };
+                let items = array_contents(heap, array_ref)?;
+                let slice = usize::try_from(start)
+                    .ok()
+                    .zip(usize::try_from(end).ok())
+                    .and_then(|(start, end)| items.get(start..end))
+                    .ok_or_else(|| {
+                        log::error!(
+                            "Slice {}..{} out of bounds for array of length {}",
+                            start,
+                            end,
+                            items.len()
+                        );
+                        VmError::IndexOutOfBounds
+                    })?
+                    .to_vec();
+                // The new array is an additional owner of every copied reference.
+                for value in &slice {
+                    if let Value::Reference(address) = value {
+                        increment_reference(heap, *address)?;
+                    }
+                }
+                let address = heap.allocate(HeapObject::Array(slice, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::NewChannel => {
+                let (sender, receiver) = tokio::sync::mpsc::channel(100);
+                let address = heap.allocate(HeapObject::Channel(sender, receiver, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::ChannelSend => {
+                let channel_ref = pop_value(execution, heap)?;
+                let message = pop_value(execution, heap)?;
+                let sender = match channel_ref {
+                    Value::Reference(address) => match heap.get(address) {
+                        Some(HeapObject::Channel(sender, _, _)) => sender.clone(),
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    _ => return Err(VmError::TypeMismatch("ChannelSend")),
+                };
+                // The queued message keeps a count of its own, as with
+                // `SendMessage`.
+                if let Value::Reference(address) = message {
+                    increment_reference(heap, address)?;
+                }
+                if sender.try_send(message).is_err() {
+                    if let Value::Reference(address) = message {
+                        decrement_reference(heap, address)?;
+                    }
+                    return Err(VmError::MailboxFull(1));
+                }
+                Ok(())
+            }
+            OpCode::ChannelRecv => {
+                let channel_ref = pop_value(execution, heap)?;
+                let message = match channel_ref {
+                    Value::Reference(address) => match heap.get_mut(address) {
+                        Some(HeapObject::Channel(_, receiver, _)) => {
+                            receiver.try_recv().map_err(|_| VmError::MailboxEmpty)?
+                        }
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    _ => return Err(VmError::TypeMismatch("ChannelRecv")),
+                };
+                // The queued count moves to the stack.
+                if let Value::Reference(address) = message {
+                    if heap.get(address).is_none() {
                         return Err(VmError::InvalidReference);
                     }
-                    push_value(execution, heap, Value::Reference(addr))
+                }
+                push_owned(execution, message)
+            }
+            OpCode::SendAll => {
+                let actor_ref = pop_value(execution, heap)?;
+                let batch = pop_value(execution, heap)?;
+                let sender = match actor_ref {
+                    Value::Reference(address) => match heap.get(address) {
+                        Some(
+                            HeapObject::Actor(_, sender, _) | HeapObject::Channel(sender, _, _),
+                        ) => sender.clone(),
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    _ => return Err(VmError::TypeMismatch("SendAll")),
+                };
+                let messages = array_contents(heap, batch)?.clone();
+                let permits = match sender.try_reserve_many(messages.len()) {
+                    Ok(permits) => permits,
+                    Err(err) => {
+                        push_value(execution, heap, batch)?;
+                        push_value(execution, heap, actor_ref)?;
+                        return Err(match err {
+                            TrySendError::Full(()) => VmError::MailboxFull(messages.len()),
+                            TrySendError::Closed(()) => VmError::MailboxClosed,
+                        });
+                    }
+                };
+                for (permit, message) in permits.zip(messages) {
+                    // Each queued reference carries its own count, as with
+                    // `SendMessage`; the array keeps the ones it holds.
+                    if let Value::Reference(address) = message {
+                        increment_reference(heap, address)?;
+                    }
+                    permit.send(message);
+                }
+                push_value(execution, heap, actor_ref)
+            }
+            OpCode::IsValid => {
+                let value = execution
+                    .stack
+                    .pop()
+                    .ok_or(VmError::StackUnderflowFor("IsValid"))?;
+                let valid = match value {
+                    Value::Reference(address) => {
+                        let live = heap.get(address).is_some();
+                        if live {
+                            decrement_reference(heap, address)?;
+                        }
+                        live
+                    }
+                    _ => false,
+                };
+                push_value(execution, heap, Value::Boolean(valid))
+            }
+            OpCode::RefCount => {
+                let count = match execution.stack.last() {
+                    Some(Value::Reference(address)) => heap
+                        .get(*address)
+                        .map(HeapObject::ref_count)
+                        .ok_or(VmError::InvalidReference)?,
+                    Some(_) => return Err(VmError::TypeMismatch("RefCount")),
+                    None => return Err(VmError::StackUnderflowFor("RefCount")),
+                };
+                pop_value(execution, heap)?;
+                push_value(execution, heap, Value::Integer(count as i32))
+            }
+            OpCode::RefEq => {
+                let len = execution.stack.len();
+                if len < 2 {
+                    return Err(VmError::StackUnderflowFor("RefEq"));
+                }
+                let (Value::Reference(a), Value::Reference(b)) =
+                    (execution.stack[len - 2], execution.stack[len - 1])
+                else {
+                    return Err(VmError::TypeMismatch("RefEq"));
+                };
+                pop_value(execution, heap)?;
+                pop_value(execution, heap)?;
+                push_value(execution, heap, Value::Boolean(a == b))
+            }
+            OpCode::ActorIp => {
+                let ip = match execution.stack.last() {
+                    Some(Value::Reference(address)) => match heap.get(*address) {
+                        Some(HeapObject::Actor(vm, _, _) | HeapObject::Supervisor(vm, _, _)) => {
+                            vm.ip()
+                        }
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    Some(_) => return Err(VmError::TypeMismatch("ActorIp")),
+                    None => return Err(VmError::StackUnderflowFor("ActorIp")),
+                };
+                pop_value(execution, heap)?;
+                push_value(execution, heap, Value::Integer(ip as i32))
+            }
+            OpCode::StrCodePoints => {
+                let string_ref = pop_value(execution, heap)?;
+                let codes = match string_ref {
+                    Value::Reference(address) => match heap.get(address) {
+                        Some(HeapObject::String(text, _)) => {
+                            text.chars().map(|c| Value::Integer(c as i32)).collect()
+                        }
+                        _ => return Err(VmError::InvalidReference),
+                    },
+                    _ => return Err(VmError::TypeMismatch("StrCodePoints")),
+                };
+                let address = heap.allocate(HeapObject::Array(codes, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::Unbox => {
+                let container = pop_value(execution, heap)?;
+                let Value::Reference(address) = container else {
+                    return Err(VmError::TypeMismatch("Unbox"));
+                };
+                let value = match heap.get(address) {
+                    Some(HeapObject::Array(items, _)) if items.len() == 1 => items[0],
+                    Some(HeapObject::String(text, _)) => {
+                        let mut chars = text.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) => Value::Integer(c as i32),
+                            _ => return Err(VmError::TypeMismatch("Unbox")),
+                        }
+                    }
+                    Some(_) => return Err(VmError::TypeMismatch("Unbox")),
+                    None => return Err(VmError::InvalidReference),
+                };
+                push_value(execution, heap, value)
+            }
+            OpCode::ArrayToStr => {
+                let array_ref = pop_value(execution, heap)?;
+                let text = array_contents(heap, array_ref)?
+                    .iter()
+                    .map(|code| match code {
+                        Value::Integer(code) => u32::try_from(*code)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or(VmError::InvalidCodePoint(*code)),
+                        _ => Err(VmError::TypeMismatch("ArrayToStr")),
+                    })
+                    .collect::<Result<String, _>>()?;
+                let address = heap.allocate(HeapObject::String(text, 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::Rand => {
+                // The top 31 bits of the output are the best mixed.
+                let value = (execution.rng.next_u64() >> 33) as i32;
+                push_value(execution, heap, Value::Integer(value))
+            }
+            OpCode::RandRange => {
+                let hi = pop_value(execution, heap)?;
+                let lo = pop_value(execution, heap)?;
+                let (Value::Integer(lo), Value::Integer(hi)) = (lo, hi) else {
+                    return Err(VmError::TypeMismatch("RandRange"));
+                };
+                if lo >= hi {
+                    return Err(VmError::EmptyRange(lo, hi));
+                }
+                let span = (hi as i64 - lo as i64) as u64;
+                let offset = (execution.rng.next_u64() % span) as i64;
+                push_value(execution, heap, Value::Integer((lo as i64 + offset) as i32))
+            }
+            OpCode::Now => {
+                let millis = (execution.clock.0)();
+                let millis = i32::try_from(millis).map_err(|_| VmError::IntegerOverflow("Now"))?;
+                push_value(execution, heap, Value::Integer(millis))
+            }
+            OpCode::HeapSize => {
+                let size = heap.len() as i32;
+                push_value(execution, heap, Value::Integer(size))
+            }
+            OpCode::CurrentIp => {
+                let ip = execution.ip.saturating_sub(1) as i32;
+                push_value(execution, heap, Value::Integer(ip))
+            }
+            OpCode::Version => {
+                let address = heap.allocate(HeapObject::String(crate::VERSION.to_string(), 0));
+                push_value(execution, heap, Value::Reference(address))
+            }
+            OpCode::Print => {
+                let value = execution
+                    .stack
+                    .pop()
+                    .ok_or(VmError::StackUnderflowFor("Print"))?;
+                if let Value::Reference(address) = value {
+                    if heap.get(address).is_none() {
+                        writeln!(
+                            execution.output.err,
+                            "warning: Print of invalid reference {}",
+                            address
+                        )?;
+                        return writeln!(execution.output.out, "<invalid reference>")
+                            .map_err(VmError::from);
+                    }
+                }
+                let text = value.display(heap);
+                if let Value::Reference(address) = value {
+                    decrement_reference(heap, address)?;
+                }
+                writeln!(execution.output.out, "{}", text)?;
+                Ok(())
+            }
+            OpCode::ExpectDepth(expected) => {
+                let actual = execution.stack.len();
+                if actual == *expected {
+                    Ok(())
                 } else {
-                    Err(VmError::InvalidReference)
+                    log::error!("Expected stack depth {}, found {}", expected, actual);
+                    Err(VmError::UnexpectedDepth {
+                        expected: *expected,
+                        actual,
+                    })
                 }
             }
+            OpCode::Assert(message) => match pop_value(execution, heap)? {
+                Value::Boolean(true) => Ok(()),
+                Value::Boolean(false) => {
+                    let message = match message {
+                        Some(message) => message.to_string(),
+                        None => format!("at instruction {}", execution.ip.saturating_sub(1)),
+                    };
+                    log::error!("Assertion failed: {}", message);
+                    Err(VmError::AssertionFailed(message))
+                }
+                _ => Err(VmError::TypeMismatch("Assert")),
+            },
         }
     }
 }
+
+/// The name and description of every opcode, in declaration order.
+pub fn all_descriptions() -> Vec<(&'static str, &'static str)> {
+    [
+        OpCode::StoreVar(0),
+        OpCode::LoadVar(0),
+        OpCode::ClearVar(0),
+        OpCode::Tee(0),
+        OpCode::UpdateVar(0),
+        OpCode::Nop,
+        OpCode::PushConst(Value::Null),
+        OpCode::PushString(Symbol::intern("")),
+        OpCode::PushBigInt(Symbol::intern("0")),
+        OpCode::Pop,
+        OpCode::Dup,
+        OpCode::Swap,
+        OpCode::CMov,
+        OpCode::SwapN(0, 0),
+        OpCode::Add,
+        OpCode::Sub,
+        OpCode::Mul,
+        OpCode::Div,
+        OpCode::SafeDiv,
+        OpCode::Mod,
+        OpCode::Neg,
+        OpCode::Exp,
+        OpCode::Shl,
+        OpCode::Shr,
+        OpCode::ToBool,
+        OpCode::FloatEq,
+        OpCode::Jump(0),
+        OpCode::JumpIfFalse(0),
+        OpCode::JumpIfTrue(0),
+        OpCode::JumpDyn,
+        OpCode::JumpRel(0),
+        OpCode::JumpIfFalseRel(0),
+        OpCode::JumpIfTrueRel(0),
+        OpCode::Call(0),
+        OpCode::TailCall(0),
+        OpCode::Return,
+        OpCode::Halt,
+        OpCode::Yield,
+        OpCode::CallDepth,
+        OpCode::Switch(0),
+        OpCode::SpawnActor(0),
+        OpCode::Fork(0),
+        OpCode::SpawnDetached(0),
+        OpCode::SendMessage,
+        OpCode::ReceiveMessage,
+        OpCode::DrainMailbox,
+        OpCode::SendIfAlive,
+        OpCode::NewChannel,
+        OpCode::ChannelSend,
+        OpCode::ChannelRecv,
+        OpCode::Select,
+        OpCode::SendAll,
+        OpCode::JoinActor,
+        OpCode::SpawnSupervisor(0),
+        OpCode::SetStrategy(0),
+        OpCode::SetStrategyDyn,
+        OpCode::GetStrategy,
+        OpCode::RestartChild(0),
+        OpCode::NewBytes,
+        OpCode::BytesGet,
+        OpCode::BytesLen,
+        OpCode::BytesSet,
+        OpCode::Serialize,
+        OpCode::Deserialize,
+        OpCode::NewSet,
+        OpCode::SetAdd,
+        OpCode::SetHas,
+        OpCode::SetUnion,
+        OpCode::SetIntersect,
+        OpCode::NewCounter,
+        OpCode::CounterInc,
+        OpCode::CounterGet,
+        OpCode::NewArray,
+        OpCode::ArrayPush,
+        OpCode::Collect(0),
+        OpCode::Spread,
+        OpCode::ArraySlice,
+        OpCode::StrCodePoints,
+        OpCode::Unbox,
+        OpCode::ArrayToStr,
+        OpCode::ArrayMap(0),
+        OpCode::ArrayFilter(0),
+        OpCode::ArrayReduce(0),
+        OpCode::Rand,
+        OpCode::RandRange,
+        OpCode::Now,
+        OpCode::HeapSize,
+        OpCode::IsValid,
+        OpCode::RefCount,
+        OpCode::RefEq,
+        OpCode::ActorIp,
+        OpCode::CurrentIp,
+        OpCode::Version,
+        OpCode::Assert(None),
+        OpCode::ExpectDepth(0),
+        OpCode::Print,
+    ]
+    .iter()
+    .map(|opcode| (opcode.name(), opcode.describe()))
+    .collect()
+}
+
+/// The description of the opcode called `name`, as for a REPL's `:help`.
+pub fn description(name: &str) -> Option<&'static str> {
+    all_descriptions()
+        .into_iter()
+        .find_map(|(opcode, description)| (opcode == name).then_some(description))
+}
@@ -0,0 +1,34 @@
+// src/vm/rng.rs
+
+/// Seed used by VMs that never call [`VM::set_seed`](crate::vm::VM::set_seed).
+pub const DEFAULT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// A small xorshift64* generator backing `Rand` and `RandRange`.
+///
+/// It is fast and reproducible, not cryptographically secure.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator from `seed`. Xorshift cannot leave an all-zero
+    /// state, so a zero seed is replaced with [`DEFAULT_SEED`].
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 { DEFAULT_SEED } else { seed };
+        Self { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEED)
+    }
+}
@@ -0,0 +1,75 @@
+// src/vm/snapshot.rs
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::vm::execution::ExecutionContext;
+use crate::vm::heap::{Heap, HeapObject};
+use crate::vm::value::Value;
+
+/// The recoverable state of a VM between two instructions, taken with
+/// [`VM::snapshot`](crate::vm::VM::snapshot) and applied with
+/// [`VM::restore`](crate::vm::VM::restore).
+///
+/// Only plain data objects (arrays, strings, bytes, big integers, and sets)
+/// and counters are copied from the heap; a copied counter shares its count
+/// with the original. Actors, supervisors, modules, native functions, and
+/// channels own state that cannot be copied, so references to them only
+/// survive a restore into the VM that still holds them.
+#[derive(Debug)]
+pub struct VmSnapshot {
+    pub ip: usize,
+    pub stack: Vec<Value>,
+    pub locals: HashMap<usize, Value>,
+    pub call_stack: Vec<usize>,
+    /// Copied data objects with their addresses and reference counts.
+    pub heap: Vec<(usize, HeapObject)>,
+}
+
+impl VmSnapshot {
+    pub(crate) fn capture(execution: &ExecutionContext, heap: &Heap) -> Self {
+        VmSnapshot {
+            ip: execution.ip,
+            stack: execution.stack.clone(),
+            locals: execution.locals.clone(),
+            call_stack: execution.call_stack.clone(),
+            heap: heap
+                .iter()
+                .filter_map(|(address, object)| Some((address, object.try_clone()?)))
+                .collect(),
+        }
+    }
+}
+
+impl Clone for VmSnapshot {
+    fn clone(&self) -> Self {
+        VmSnapshot {
+            ip: self.ip,
+            stack: self.stack.clone(),
+            locals: self.locals.clone(),
+            call_stack: self.call_stack.clone(),
+            // Only data objects are ever captured, and those always copy.
+            heap: self
+                .heap
+                .iter()
+                .filter_map(|(address, object)| Some((*address, object.try_clone()?)))
+                .collect(),
+        }
+    }
+}
+
+/// A host callback invoked with a fresh snapshot every `every` steps.
+pub(crate) struct Checkpoint {
+    pub(crate) every: usize,
+    pub(crate) steps: usize,
+    pub(crate) callback: Box<dyn FnMut(&VmSnapshot) + Send>,
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("every", &self.every)
+            .field("steps", &self.steps)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,64 @@
+// src/vm/symbol.rs
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// An interned string constant.
+///
+/// Opcodes must stay `Copy`, so string operands are stored once in a
+/// process-wide table and referenced by index. Interning the same text twice
+/// yields the same symbol.
+///
+/// The table is never pruned: every distinct string literal, big integer
+/// literal, and `Assert` message ever compiled stays in memory until the
+/// process exits, even after the bytecode using it is dropped. Hosts that
+/// compile an unbounded stream of distinct scripts grow without bound and
+/// should run them in a separate process they can restart.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<Arc<str>, u32>,
+    strings: Vec<Arc<str>>,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Symbol {
+    pub fn intern(text: &str) -> Symbol {
+        if let Some(&id) = interner().read().unwrap().ids.get(text) {
+            return Symbol(id);
+        }
+        let mut interner = interner().write().unwrap();
+        // Another thread may have interned the text since the read lock.
+        if let Some(&id) = interner.ids.get(text) {
+            return Symbol(id);
+        }
+        let id = interner.strings.len() as u32;
+        let text: Arc<str> = Arc::from(text);
+        interner.strings.push(text.clone());
+        interner.ids.insert(text, id);
+        Symbol(id)
+    }
+
+    pub fn as_str(&self) -> Arc<str> {
+        interner().read().unwrap().strings[self.0 as usize].clone()
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.as_str())
+    }
+}
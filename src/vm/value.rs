@@ -1,7 +1,10 @@
 // src/vm/value.rs
 
 use crate::vm::error::VmError;
+use crate::vm::heap::{Heap, HeapObject};
 use log;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Value {
@@ -12,10 +15,307 @@ pub enum Value {
     Null,
 }
 
+/// `Value` is usable as a map or set key.
+///
+/// Floats keep the derived `PartialEq`, so `NaN` is never equal to itself and
+/// `Eq` is technically violated for `NaN` payloads; such keys can be inserted
+/// but never looked up again. Callers that need reliable float keys should
+/// reject `NaN` first (see [`Value::is_hashable`]).
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Integer(i) => i.hash(state),
+            // Floats hash by bit pattern. `-0.0 == 0.0` under `PartialEq`, so
+            // both zeroes are folded into one pattern to keep `Hash` consistent
+            // with equality, and every `NaN` is canonicalized.
+            Value::Float(f) => {
+                let bits = if *f == 0.0 {
+                    0.0f64.to_bits()
+                } else if f.is_nan() {
+                    f64::NAN.to_bits()
+                } else {
+                    f.to_bits()
+                };
+                bits.hash(state);
+            }
+            Value::Boolean(b) => b.hash(state),
+            Value::Reference(address) => address.hash(state),
+            Value::Null => {}
+        }
+    }
+}
+
+const TAG_INTEGER: u8 = 0;
+const TAG_FLOAT: u8 = 1;
+const TAG_BOOLEAN: u8 = 2;
+const TAG_REFERENCE: u8 = 3;
+const TAG_NULL: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_ARRAY: u8 = 6;
+
+/// How deeply arrays may nest in input to [`Value::deserialize`], so hostile
+/// input cannot exhaust the native stack while it is decoded.
+pub const MAX_DECODE_DEPTH: usize = 128;
+
+#[allow(clippy::should_implement_trait)]
 impl Value {
+    /// Renders the value for people, following references into `heap`:
+    /// arrays show their elements as `[1, 2, 3]`, sets as `{1, 2}` with
+    /// elements sorted by rendering, and strings and big integers their
+    /// contents. Strings inside a collection are quoted. An array or set
+    /// that contains itself shows as `[...]` or `{...}` where it recurs, and
+    /// other heap objects show their summary.
+    pub fn display(&self, heap: &Heap) -> String {
+        self.render(heap, &mut HashSet::new(), false)
+    }
+
+    fn render(&self, heap: &Heap, open: &mut HashSet<usize>, nested: bool) -> String {
+        let address = match self {
+            Value::Integer(i) => return i.to_string(),
+            Value::Float(f) => return f.to_string(),
+            Value::Boolean(b) => return b.to_string(),
+            Value::Null => return "null".to_string(),
+            Value::Reference(address) => *address,
+        };
+        match heap.get(address) {
+            None => "<invalid reference>".to_string(),
+            Some(HeapObject::String(text, _)) if nested => format!("{:?}", text),
+            Some(HeapObject::String(text, _)) => text.clone(),
+            Some(HeapObject::BigInt(value, _)) => value.to_string(),
+            Some(HeapObject::Array(items, _)) => match render_items(items, address, heap, open) {
+                Some(items) => format!("[{}]", items.join(", ")),
+                None => "[...]".to_string(),
+            },
+            Some(HeapObject::Set(items, _)) => match render_items(items, address, heap, open) {
+                Some(mut items) => {
+                    items.sort();
+                    format!("{{{}}}", items.join(", "))
+                }
+                None => "{...}".to_string(),
+            },
+            Some(object) => object.summary(),
+        }
+    }
+
+    /// Encodes the value as a one-byte tag followed by its payload in
+    /// little-endian order.
+    ///
+    /// A `Reference` is encoded as its raw heap address. It only identifies
+    /// the same object when decoded into the VM that produced it and only
+    /// while that object is still alive; the referenced contents are not
+    /// serialized.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(9);
+        match self {
+            Value::Integer(i) => {
+                bytes.push(TAG_INTEGER);
+                bytes.extend_from_slice(&i.to_le_bytes());
+            }
+            Value::Float(f) => {
+                bytes.push(TAG_FLOAT);
+                bytes.extend_from_slice(&f.to_le_bytes());
+            }
+            Value::Boolean(b) => {
+                bytes.push(TAG_BOOLEAN);
+                bytes.push(*b as u8);
+            }
+            Value::Reference(address) => {
+                bytes.push(TAG_REFERENCE);
+                bytes.extend_from_slice(&(*address as u64).to_le_bytes());
+            }
+            Value::Null => bytes.push(TAG_NULL),
+        }
+        bytes
+    }
+
+    /// Decodes a value produced by [`Value::to_bytes`]. The input must hold
+    /// exactly one encoded value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Value, VmError> {
+        let (&tag, payload) = bytes
+            .split_first()
+            .ok_or_else(|| VmError::InvalidEncoding("empty input".to_string()))?;
+        let invalid_length = || {
+            VmError::InvalidEncoding(format!("tag {} with {} payload bytes", tag, payload.len()))
+        };
+        match tag {
+            TAG_INTEGER => payload
+                .try_into()
+                .map(|b| Value::Integer(i32::from_le_bytes(b)))
+                .map_err(|_| invalid_length()),
+            TAG_FLOAT => payload
+                .try_into()
+                .map(|b| Value::Float(f64::from_le_bytes(b)))
+                .map_err(|_| invalid_length()),
+            TAG_BOOLEAN => match payload {
+                [0] => Ok(Value::Boolean(false)),
+                [1] => Ok(Value::Boolean(true)),
+                [_] => Err(VmError::InvalidEncoding(format!(
+                    "invalid boolean byte {}",
+                    payload[0]
+                ))),
+                _ => Err(invalid_length()),
+            },
+            TAG_REFERENCE => {
+                let address = payload
+                    .try_into()
+                    .map(u64::from_le_bytes)
+                    .map_err(|_| invalid_length())?;
+                usize::try_from(address)
+                    .map(Value::Reference)
+                    .map_err(|_| VmError::InvalidEncoding(format!("address {}", address)))
+            }
+            TAG_NULL if payload.is_empty() => Ok(Value::Null),
+            TAG_NULL => Err(invalid_length()),
+            _ => Err(VmError::InvalidEncoding(format!("unknown tag {}", tag))),
+        }
+    }
+
+    /// Encodes the value together with the strings and arrays it refers to,
+    /// so it can be rebuilt in another VM's heap with
+    /// [`Value::deserialize`]. Scalars encode as in [`Value::to_bytes`]; a
+    /// string is its tag, a little-endian `u32` byte length, and its UTF-8
+    /// bytes; an array is its tag, a `u32` element count, and each element
+    /// encoded in turn.
+    ///
+    /// Other heap objects fail with [`VmError::TypeMismatch`], and an array
+    /// that contains itself with [`VmError::CyclicValue`]. An object
+    /// reachable along several paths is encoded, and later rebuilt, once per
+    /// path.
+    pub fn serialize(&self, heap: &Heap) -> Result<Vec<u8>, VmError> {
+        let mut bytes = Vec::new();
+        self.write(heap, &mut HashSet::new(), &mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn write(
+        &self,
+        heap: &Heap,
+        open: &mut HashSet<usize>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), VmError> {
+        let Value::Reference(address) = *self else {
+            out.extend(self.to_bytes());
+            return Ok(());
+        };
+        match heap.get(address) {
+            Some(HeapObject::String(text, _)) => {
+                out.push(TAG_STRING);
+                out.extend(encoded_len(text.len())?);
+                out.extend(text.as_bytes());
+            }
+            Some(HeapObject::Array(items, _)) => {
+                if !open.insert(address) {
+                    return Err(VmError::CyclicValue);
+                }
+                out.push(TAG_ARRAY);
+                out.extend(encoded_len(items.len())?);
+                for item in items {
+                    item.write(heap, open, out)?;
+                }
+                open.remove(&address);
+            }
+            Some(_) => return Err(VmError::TypeMismatch("Serialize")),
+            None => return Err(VmError::InvalidReference),
+        }
+        Ok(())
+    }
+
+    /// Rebuilds a value produced by [`Value::serialize`], allocating fresh
+    /// strings and arrays in `heap`. The returned value's own object has no
+    /// references yet; objects nested in it hold one each. The input must
+    /// hold exactly one encoded value, and encoded raw references are
+    /// rejected since they mean nothing in another heap. Arrays nested more
+    /// than [`MAX_DECODE_DEPTH`] deep are rejected too.
+    ///
+    /// On failure nothing is left behind in `heap`: objects already rebuilt
+    /// from the input are removed again.
+    pub fn deserialize(bytes: &[u8], heap: &mut Heap) -> Result<Value, VmError> {
+        let mut input = bytes;
+        let mut allocated = Vec::new();
+        let result = Value::read(&mut input, heap, 0, &mut allocated).and_then(|value| {
+            if input.is_empty() {
+                Ok(value)
+            } else {
+                Err(VmError::InvalidEncoding(format!(
+                    "{} trailing bytes",
+                    input.len()
+                )))
+            }
+        });
+        if result.is_err() {
+            for address in allocated {
+                heap.remove(address);
+            }
+        }
+        result
+    }
+
+    /// Decodes one value nested inside `depth` arrays, recording the address of every
+    /// object it allocates in `allocated`.
+    fn read(
+        input: &mut &[u8],
+        heap: &mut Heap,
+        depth: usize,
+        allocated: &mut Vec<usize>,
+    ) -> Result<Value, VmError> {
+        let tag = take(input, 1)?[0];
+        match tag {
+            TAG_INTEGER => Value::from_bytes(&[&[tag], take(input, 4)?].concat()),
+            TAG_FLOAT => Value::from_bytes(&[&[tag], take(input, 8)?].concat()),
+            TAG_BOOLEAN => Value::from_bytes(&[&[tag], take(input, 1)?].concat()),
+            TAG_NULL => Ok(Value::Null),
+            TAG_STRING => {
+                let len = decoded_len(input)?;
+                let text = String::from_utf8(take(input, len)?.to_vec())
+                    .map_err(|e| VmError::InvalidEncoding(e.to_string()))?;
+                let address = heap.allocate(HeapObject::String(text, 0));
+                allocated.push(address);
+                Ok(Value::Reference(address))
+            }
+            TAG_ARRAY => {
+                if depth == MAX_DECODE_DEPTH {
+                    return Err(VmError::InvalidEncoding(format!(
+                        "arrays nested more than {} deep",
+                        MAX_DECODE_DEPTH
+                    )));
+                }
+                let len = decoded_len(input)?;
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    let item = Value::read(input, heap, depth + 1, allocated)?;
+                    if let Value::Reference(address) = item {
+                        if let Some(object) = heap.get_mut(address) {
+                            object.increment_ref();
+                        }
+                    }
+                    items.push(item);
+                }
+                let address = heap.allocate(HeapObject::Array(items, 0));
+                allocated.push(address);
+                Ok(Value::Reference(address))
+            }
+            TAG_REFERENCE => Err(VmError::InvalidEncoding(
+                "raw reference in serialized value".to_string(),
+            )),
+            _ => Err(VmError::InvalidEncoding(format!("unknown tag {}", tag))),
+        }
+    }
+
+    /// Returns `false` for values that cannot round-trip through a hashed
+    /// collection, which today is only `Float(NaN)`.
+    pub fn is_hashable(&self) -> bool {
+        !matches!(self, Value::Float(f) if f.is_nan())
+    }
+
     pub fn add(self, other: Value) -> Result<Value, VmError> {
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a + b)),
+            (Value::Integer(a), Value::Integer(b)) => a
+                .checked_add(b)
+                .map(Value::Integer)
+                .ok_or(VmError::IntegerOverflow("Add")),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
             _ => Err(VmError::TypeMismatch("Add")),
         }
@@ -23,7 +323,10 @@ impl Value {
 
     pub fn sub(self, other: Value) -> Result<Value, VmError> {
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a - b)),
+            (Value::Integer(a), Value::Integer(b)) => a
+                .checked_sub(b)
+                .map(Value::Integer)
+                .ok_or(VmError::IntegerOverflow("Sub")),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
             _ => Err(VmError::TypeMismatch("Sub")),
         }
@@ -31,7 +334,10 @@ impl Value {
 
     pub fn mul(self, other: Value) -> Result<Value, VmError> {
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(a * b)),
+            (Value::Integer(a), Value::Integer(b)) => a
+                .checked_mul(b)
+                .map(Value::Integer)
+                .ok_or(VmError::IntegerOverflow("Mul")),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
             _ => Err(VmError::TypeMismatch("Mul")),
         }
@@ -62,3 +368,45 @@ impl Value {
         }
     }
 }
+
+fn encoded_len(len: usize) -> Result<[u8; 4], VmError> {
+    u32::try_from(len)
+        .map(u32::to_le_bytes)
+        .map_err(|_| VmError::InvalidEncoding(format!("length {} does not fit in u32", len)))
+}
+
+fn decoded_len(input: &mut &[u8]) -> Result<usize, VmError> {
+    let bytes = take(input, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// Splits the first `len` bytes off `input`.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], VmError> {
+    if input.len() < len {
+        return Err(VmError::InvalidEncoding(
+            "unexpected end of input".to_string(),
+        ));
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Ok(head)
+}
+
+/// Renders the elements of the collection at `address`, or `None` if it is
+/// already being rendered further up, which means it contains itself.
+fn render_items<'a>(
+    items: impl IntoIterator<Item = &'a Value>,
+    address: usize,
+    heap: &Heap,
+    open: &mut HashSet<usize>,
+) -> Option<Vec<String>> {
+    if !open.insert(address) {
+        return None;
+    }
+    let items = items
+        .into_iter()
+        .map(|item| item.render(heap, open, true))
+        .collect();
+    open.remove(&address);
+    Some(items)
+}
@@ -1,12 +1,71 @@
 // src/vm/vm.rs
 
+use crate::compiler::CompiledProgram;
 use crate::vm::error::VmError;
-use crate::vm::execution::ExecutionContext;
+use crate::vm::execution::{Clock, ExecutionContext, MessageHook};
 use crate::vm::heap::{Heap, HeapObject};
 use crate::vm::opcodes::OpCode;
+use crate::vm::rng::Rng;
+use crate::vm::snapshot::{Checkpoint, VmSnapshot};
 use crate::vm::value::Value;
 
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
+
+/// Messages a mailbox queues before senders wait, unless a [`VmBuilder`]
+/// sets another capacity.
+///
+/// [`VmBuilder`]: crate::vm::builder::VmBuilder
+pub const DEFAULT_MAILBOX_CAPACITY: usize = 100;
+
+/// Why a run of the VM ended without an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunResult {
+    /// Execution ran past the last instruction.
+    Completed,
+    /// The program executed `Halt`.
+    Halted,
+    /// The program executed `Yield`; running again continues after it.
+    Yielded,
+    /// The time given to [`VM::run_for`] ran out.
+    TimedOut,
+    /// The step observer asked to pause; running again continues with the
+    /// instruction it was shown.
+    Paused,
+    /// Execution reached the breakpoint at this address; running again
+    /// continues with the instruction there.
+    BreakpointHit(usize),
+}
+
+/// What a step observer wants the VM to do with the instruction it is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepAction {
+    /// Execute the instruction and keep going.
+    Continue,
+    /// Stop before the instruction with [`RunResult::Paused`].
+    Pause,
+}
+
+/// A host callback shown each instruction's address and opcode before it
+/// executes; see [`VM::set_step_observer`].
+pub type StepCallback = Box<dyn FnMut(usize, &OpCode) -> StepAction + Send>;
+
+struct StepObserver(StepCallback);
+
+impl fmt::Debug for StepObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StepObserver").finish_non_exhaustive()
+    }
+}
+
+/// Source of VM ids, so every VM (and so every actor) gets a distinct one.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Debug)]
 pub struct VM {
@@ -14,23 +73,241 @@ pub struct VM {
     heap: Heap,
     pub mailbox: Receiver<Value>,
     _supervisor: Option<Sender<usize>>,
+    entry: usize,
+    id: u64,
+    checkpoint: Option<Checkpoint>,
+    step_observer: Option<StepObserver>,
+    breakpoints: HashSet<usize>,
+    /// The address the last pause or breakpoint stopped at, so resuming
+    /// executes that instruction instead of stopping on it again.
+    paused_at: Option<usize>,
+    strategy: usize,
 }
 
 impl VM {
     pub fn new(bytecode: Vec<OpCode>, supervisor: Option<Sender<usize>>) -> (Self, Sender<Value>) {
-        let (tx, rx) = mpsc::channel(100);
-        log::info!("Initializing VM with {} opcodes", bytecode.len());
+        Self::with_mailbox(bytecode, supervisor, DEFAULT_MAILBOX_CAPACITY)
+    }
+
+    pub(crate) fn with_mailbox(
+        bytecode: Vec<OpCode>,
+        supervisor: Option<Sender<usize>>,
+        capacity: usize,
+    ) -> (Self, Sender<Value>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        log::info!(
+            "[vm {}] Initializing VM with {} opcodes",
+            id,
+            bytecode.len()
+        );
         (
             VM {
                 execution: ExecutionContext::new(bytecode),
                 heap: Heap::new(),
                 mailbox: rx,
                 _supervisor: supervisor,
+                entry: 0,
+                id,
+                checkpoint: None,
+                step_observer: None,
+                breakpoints: HashSet::new(),
+                paused_at: None,
+                strategy: 0,
             },
             tx,
         )
     }
 
+    /// A process-wide unique id assigned when the VM is created, used to tell
+    /// actors apart in logs.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Creates a VM for a compiled program, starting at its entry point.
+    pub fn from_program(
+        program: CompiledProgram,
+        supervisor: Option<Sender<usize>>,
+    ) -> (Self, Sender<Value>) {
+        let (mut vm, tx) = VM::new(program.bytecode, supervisor);
+        vm.set_entry(program.entry);
+        (vm, tx)
+    }
+
+    /// Pre-sizes the heap to hold `capacity` objects without reallocating,
+    /// for workloads whose allocation count is known. Purely a performance
+    /// hint: the heap still grows past it.
+    pub fn with_heap_capacity(mut self, capacity: usize) -> Self {
+        let additional = capacity.saturating_sub(self.heap.len());
+        self.heap.reserve(additional);
+        self
+    }
+
+    /// Bounds the stack at `capacity` values, allocated up front. A push
+    /// beyond the bound fails with [`VmError::StackOverflow`] instead of
+    /// growing the stack, so it never reallocates.
+    pub fn with_stack_capacity(mut self, capacity: usize) -> Self {
+        let stack = &mut self.execution.stack;
+        stack.reserve_exact(capacity.saturating_sub(stack.len()));
+        self.execution.stack_capacity = Some(capacity);
+        self
+    }
+
+    /// Detaches the actor at `address` from the heap, handing back its VM and
+    /// the sender for its mailbox so the host can run it elsewhere. Returns
+    /// `None` if `address` does not hold an actor.
+    ///
+    /// References to the actor still held by this VM become invalid; the
+    /// `IsValid` opcode reports `false` for them.
+    pub fn take_actor(&mut self, address: usize) -> Option<(VM, Sender<Value>)> {
+        if !matches!(self.heap.get(address), Some(HeapObject::Actor(..))) {
+            return None;
+        }
+        match self.heap.remove(address) {
+            Some(HeapObject::Actor(vm, sender, _)) => {
+                log::info!("[vm {}] Detached actor {} at {}", self.id, vm.id(), address);
+                Some((vm, sender))
+            }
+            _ => None,
+        }
+    }
+
+    /// Caps how many actors and supervisors may be alive on this VM's heap
+    /// at once. Spawning beyond the cap fails with
+    /// [`VmError::ActorLimitExceeded`]; collected or detached actors no
+    /// longer count. Spawned actors inherit the cap for their own heaps.
+    pub fn with_max_actors(mut self, max_actors: usize) -> Self {
+        self.execution.max_actors = Some(max_actors);
+        self
+    }
+
+    /// Makes `LoadVar` of a local that was never stored (or was cleared)
+    /// push `Null` instead of failing with [`VmError::VariableNotFound`].
+    ///
+    /// This suits programs that treat unset locals as empty, but it also
+    /// hides misspelled indices and loads before stores, which then surface
+    /// later as type mismatches on `Null`, if at all. Off by default.
+    pub fn with_default_locals_to_null(mut self, enabled: bool) -> Self {
+        self.execution.default_locals_to_null = enabled;
+        self
+    }
+
+    /// Reseeds the generator behind `Rand` and `RandRange`. VMs given the
+    /// same seed draw the same sequence.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.execution.rng = Rng::new(seed);
+    }
+
+    /// Replaces the millisecond clock read by `Now`, e.g. with a fixed value
+    /// in tests.
+    pub fn set_clock(&mut self, clock: impl Fn() -> u64 + Send + 'static) {
+        self.execution.clock = Clock(Box::new(clock));
+    }
+
+    /// Stops every actor and supervisor spawned by this VM; see
+    /// [`Heap::shutdown`].
+    pub fn shutdown(&mut self) {
+        self.heap.shutdown();
+    }
+
+    /// Captures the VM's current state; see [`VmSnapshot`] for what is kept.
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot::capture(&self.execution, &self.heap)
+    }
+
+    /// Returns the VM to the state captured in `snapshot`, which may come
+    /// from another VM running the same bytecode.
+    pub fn restore(&mut self, snapshot: &VmSnapshot) {
+        self.execution.ip = snapshot.ip;
+        self.execution.stack = snapshot.stack.clone();
+        self.execution.locals = snapshot.locals.clone();
+        self.execution.call_stack = snapshot.call_stack.clone();
+        self.heap.restore(
+            snapshot
+                .heap
+                .iter()
+                .filter_map(|(address, object)| Some((*address, object.try_clone()?))),
+        );
+        log::info!("[vm {}] Restored snapshot at ip {}", self.id, snapshot.ip);
+    }
+
+    /// Calls `callback` with a snapshot after every `every` executed
+    /// instructions, counted across runs, so the host can persist progress
+    /// and later [`VM::restore`] it. While a checkpoint is set, instructions
+    /// are executed one at a time rather than in synchronous batches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `every` is zero.
+    pub fn set_checkpoint(&mut self, every: usize, callback: Box<dyn FnMut(&VmSnapshot) + Send>) {
+        assert!(every > 0, "checkpoint interval must be positive");
+        self.checkpoint = Some(Checkpoint {
+            every,
+            steps: 0,
+            callback,
+        });
+    }
+
+    pub fn clear_checkpoint(&mut self) {
+        self.checkpoint = None;
+    }
+
+    /// Shows `observer` the address and opcode of every instruction before
+    /// [`VM::run`] executes it. Returning [`StepAction::Pause`] ends the run
+    /// with [`RunResult::Paused`] without executing the instruction; the
+    /// next run executes it without asking again, so a debugger can step
+    /// by pausing on every instruction. While an observer is set,
+    /// instructions are executed one at a time rather than in synchronous
+    /// batches.
+    pub fn set_step_observer(&mut self, observer: StepCallback) {
+        self.step_observer = Some(StepObserver(observer));
+    }
+
+    /// Removes the observer installed by [`VM::set_step_observer`].
+    pub fn clear_step_observer(&mut self) {
+        self.step_observer = None;
+    }
+
+    /// Makes [`VM::run`] stop with [`RunResult::BreakpointHit`] before
+    /// executing the instruction at any of `breakpoints`, replacing the
+    /// previous set. Like a pause, the next run executes that instruction
+    /// without stopping on it again. Breakpoints may be changed while
+    /// stopped; while any are set, instructions are executed one at a time.
+    pub fn set_breakpoints(&mut self, breakpoints: HashSet<usize>) {
+        self.breakpoints = breakpoints;
+    }
+
+    /// Removes every breakpoint.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> &HashSet<usize> {
+        &self.breakpoints
+    }
+
+    /// Installs a callback that sees every message `ReceiveMessage` or
+    /// `DrainMailbox` takes from the mailbox, before it is pushed. Replaces any previous hook.
+    pub fn on_message(&mut self, hook: Box<dyn FnMut(&Value) + Send>) {
+        self.execution.on_message = Some(MessageHook(hook));
+    }
+
+    /// Removes the hook installed by [`VM::on_message`].
+    pub fn clear_message_hook(&mut self) {
+        self.execution.on_message = None;
+    }
+
+    /// Redirects program output, which defaults to stdout.
+    pub fn set_output(&mut self, out: impl Write + Send + 'static) {
+        self.execution.output.out = Box::new(out);
+    }
+
+    /// Redirects warnings, which default to stderr.
+    pub fn set_error_output(&mut self, err: impl Write + Send + 'static) {
+        self.execution.output.err = Box::new(err);
+    }
+
     pub fn pop_stack(&mut self) -> Result<Value, VmError> {
         match self.execution.stack.pop() {
             Some(value) => {
@@ -38,14 +315,21 @@ impl VM {
                     if let Some(object) = self.heap.get_mut(address) {
                         object.decrement_ref();
                     } else {
-                        log::error!("Attempted to pop invalid heap reference: {}", address);
+                        log::error!(
+                            "[vm {}] Attempted to pop invalid heap reference: {}",
+                            self.id,
+                            address
+                        );
                         return Err(VmError::InvalidReference);
                     }
                 }
                 Ok(value)
             }
             None => {
-                log::error!("Attempted to pop value from an empty stack");
+                log::error!(
+                    "[vm {}] Attempted to pop value from an empty stack",
+                    self.id
+                );
                 Err(VmError::StackUnderflow)
             }
         }
@@ -56,47 +340,319 @@ impl VM {
     }
 
     pub fn heap_ref_count(&self, address: usize) -> Option<usize> {
-        self.heap.get(address).map(|object| match object {
-            HeapObject::Array(_, rc)
-            | HeapObject::String(_, rc)
-            | HeapObject::NativeFunction(_, rc)
-            | HeapObject::Actor(_, _, rc)
-            | HeapObject::Supervisor(_, _, rc) => *rc,
-            HeapObject::Module { ref_count, .. } => *ref_count,
-        })
+        self.heap.get(address).map(HeapObject::ref_count)
+    }
+
+    /// The sum of the reference counts of every object on the heap.
+    pub fn live_reference_count(&self) -> usize {
+        self.heap.iter().map(|(_, object)| object.ref_count()).sum()
+    }
+
+    /// Checks that every reference count is owned by a reference the VM can
+    /// see: one on the stack, in a local, or inside an object on the heap.
+    /// Counts beyond those are leaks and fail with
+    /// [`VmError::AssertionFailed`] listing each leaking address.
+    ///
+    /// Messages still queued in a mailbox own counts the VM cannot see, so
+    /// this is meant for the end of a program, once messages are received.
+    pub fn assert_no_leaks(&self) -> Result<(), VmError> {
+        let mut holders: HashMap<usize, usize> = HashMap::new();
+        let locals = self.execution.locals.values();
+        let contents = self.heap.iter().flat_map(|(_, object)| object.contents());
+        for value in self.execution.stack.iter().chain(locals).chain(contents) {
+            if let Value::Reference(address) = value {
+                *holders.entry(*address).or_default() += 1;
+            }
+        }
+        let mut leaks: Vec<_> = self
+            .heap
+            .iter()
+            .filter_map(|(address, object)| {
+                let held = holders.get(&address).copied().unwrap_or(0);
+                (object.ref_count() > held).then(|| (address, object.ref_count(), held))
+            })
+            .collect();
+        if leaks.is_empty() {
+            return Ok(());
+        }
+        leaks.sort_unstable();
+        let details: Vec<_> = leaks
+            .iter()
+            .map(|(address, count, held)| {
+                format!("{} has {} references but {} holders", address, count, held)
+            })
+            .collect();
+        log::error!("[vm {}] Leaked references: {}", self.id, details.join("; "));
+        Err(VmError::AssertionFailed(format!(
+            "leaked references: {}",
+            details.join("; ")
+        )))
+    }
+
+    /// Renders every heap object with its address, kind, reference count, and
+    /// a short summary of its contents, one per line in address order.
+    pub fn dump_heap(&self) -> String {
+        let mut objects: Vec<_> = self.heap.iter().collect();
+        objects.sort_by_key(|(address, _)| *address);
+        objects
+            .into_iter()
+            .map(|(address, object)| {
+                format!(
+                    "{:>6}  rc={:<3} {}\n",
+                    address,
+                    object.ref_count(),
+                    object.summary()
+                )
+            })
+            .collect()
+    }
+
+    pub fn ip(&self) -> usize {
+        self.execution.ip
     }
 
     pub fn set_ip(&mut self, ip: usize) {
         self.execution.ip = ip;
     }
 
-    pub async fn run(&mut self) -> Result<(), VmError> {
+    /// Sets the address execution starts from, both now and after a
+    /// [`VM::reset`].
+    pub fn set_entry(&mut self, entry: usize) {
+        self.entry = entry;
+        self.execution.ip = entry;
+    }
+
+    /// The address execution starts from after a [`VM::reset`].
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    /// Returns the VM to its entry point with an empty stack, locals, and call
+    /// stack, releasing any references they held. The heap and mailbox are
+    /// left untouched, so queued messages survive a reset.
+    pub fn reset(&mut self) {
+        let locals = self.execution.locals.drain().map(|(_, value)| value);
+        for value in self.execution.stack.drain(..).chain(locals) {
+            if let Value::Reference(address) = value {
+                if let Some(object) = self.heap.get_mut(address) {
+                    object.decrement_ref();
+                }
+            }
+        }
+        self.execution.call_stack.clear();
+        self.execution.ip = self.entry;
+        log::info!("[vm {}] Reset to entry {}", self.id, self.entry);
+    }
+
+    /// Runs until the program completes, halts, or yields, reporting which in
+    /// the returned [`RunResult`].
+    pub async fn run(&mut self) -> Result<RunResult, VmError> {
+        self.run_until(std::future::pending()).await
+    }
+
+    /// The loop behind every way of running the VM. Breakpoints, the step
+    /// observer, and checkpoints apply alike; `interrupt` is polled before
+    /// each step and while it waits, and ends the run with its output as
+    /// soon as it is ready.
+    async fn run_until(
+        &mut self,
+        interrupt: impl Future<Output = Result<RunResult, VmError>>,
+    ) -> Result<RunResult, VmError> {
         if self.execution.bytecode.is_empty() {
-            log::warn!("Attempted to run VM with empty bytecode");
+            log::warn!("[vm {}] Attempted to run VM with empty bytecode", self.id);
             return Err(VmError::NoBytecode);
         }
 
+        tokio::pin!(interrupt);
         while self.execution.ip < self.execution.bytecode.len() {
-            if let Err(e) = self.execution.step(&mut self.heap, &mut self.mailbox).await {
-                log::error!("Execution error at ip {}: {}", self.execution.ip, e);
+            if let Some(pause) = self.pause_requested() {
+                return Ok(pause);
+            }
+            let result = tokio::select! {
+                biased;
+                outcome = &mut interrupt => {
+                    log::warn!(
+                        "[vm {}] Execution interrupted at ip {}: {:?}",
+                        self.id,
+                        self.execution.ip,
+                        outcome
+                    );
+                    return outcome;
+                }
+                result = self.advance() => result,
+            };
+            if let Err(e) = result {
+                log::error!(
+                    "[vm {}] Execution error at ip {}: {}",
+                    self.id,
+                    self.execution.ip,
+                    e
+                );
                 return Err(e);
             }
+            if let Some(stop) = self.stopped() {
+                return Ok(stop);
+            }
+        }
+        log::info!("[vm {}] Execution completed successfully", self.id);
+        Ok(RunResult::Completed)
+    }
+
+    /// Executes one instruction, or with no checkpoint, step observer, or
+    /// breakpoints to honour, every instruction up to and including the next
+    /// async one.
+    async fn advance(&mut self) -> Result<(), VmError> {
+        let stepwise = self.checkpoint.is_some()
+            || self.step_observer.is_some()
+            || !self.breakpoints.is_empty();
+        if stepwise {
+            let result = self.execution.step(&mut self.heap, &mut self.mailbox).await;
+            if let (Ok(()), Some(checkpoint)) = (&result, self.checkpoint.as_mut()) {
+                checkpoint.steps += 1;
+                if checkpoint.steps % checkpoint.every == 0 {
+                    let snapshot = VmSnapshot::capture(&self.execution, &self.heap);
+                    (checkpoint.callback)(&snapshot);
+                }
+            }
+            return result;
+        }
+        // Runs of opcodes that never wait execute synchronously; only an
+        // async opcode goes through an await point.
+        self.execution.run_sync(&mut self.heap)?;
+        if self.execution.stop.is_none() && self.execution.ip < self.execution.bytecode.len() {
+            self.execution
+                .step(&mut self.heap, &mut self.mailbox)
+                .await?;
         }
-        log::info!("VM execution completed successfully");
         Ok(())
     }
 
+    /// Runs like [`VM::run`], but treats finishing with more than one value
+    /// on the stack as a bug and fails with [`VmError::StackNotEmpty`]. A
+    /// single value is allowed as the program's result. A run that yielded,
+    /// paused, or hit a breakpoint is not finished and is not checked.
+    pub async fn run_strict(&mut self) -> Result<RunResult, VmError> {
+        let result = self.run().await?;
+        let depth = self.execution.stack.len();
+        let finished = !matches!(
+            result,
+            RunResult::Yielded | RunResult::Paused | RunResult::BreakpointHit(_)
+        );
+        if finished && depth > 1 {
+            log::error!("[vm {}] Program ended with {} values", self.id, depth);
+            return Err(VmError::StackNotEmpty(depth));
+        }
+        Ok(result)
+    }
+
+    /// Runs like [`VM::run`], but yields with [`RunResult::Yielded`] once
+    /// `ReceiveMessage` has taken `max_messages` messages, so a busy actor
+    /// cannot hold on to the runtime. Running again continues after the last
+    /// message taken. A budget of zero yields without running.
+    pub async fn run_bounded(&mut self, max_messages: usize) -> Result<RunResult, VmError> {
+        if max_messages == 0 {
+            return Ok(RunResult::Yielded);
+        }
+        self.execution.message_budget = Some(max_messages);
+        let result = self.run().await;
+        self.execution.message_budget = None;
+        result
+    }
+
+    /// Checks the breakpoints and asks the step observer, if any, whether
+    /// to stop before the next instruction. An instruction the VM already
+    /// stopped on is let through.
+    fn pause_requested(&mut self) -> Option<RunResult> {
+        let ip = self.execution.ip;
+        if self.paused_at.take() == Some(ip) {
+            return None;
+        }
+        let pause = if self.breakpoints.contains(&ip) {
+            RunResult::BreakpointHit(ip)
+        } else {
+            let observer = self.step_observer.as_mut()?;
+            if (observer.0)(ip, &self.execution.bytecode[ip]) == StepAction::Continue {
+                return None;
+            }
+            RunResult::Paused
+        };
+        log::info!("[vm {}] Stopped at ip {}: {:?}", self.id, ip, pause);
+        self.paused_at = Some(ip);
+        Some(pause)
+    }
+
+    /// Takes the reason `Halt` or `Yield` left for ending the run, if any.
+    fn stopped(&mut self) -> Option<RunResult> {
+        let stop = self.execution.stop.take()?;
+        log::info!(
+            "[vm {}] Execution stopped at ip {}: {:?}",
+            self.id,
+            self.execution.ip,
+            stop
+        );
+        Some(stop)
+    }
+
+    /// Runs like [`VM::run`] but returns [`VmError::Cancelled`] as soon as
+    /// `token` is cancelled, including while an opcode such as
+    /// `ReceiveMessage` is waiting.
+    ///
+    /// An interrupted opcode has already advanced the instruction pointer, so
+    /// a cancelled VM should be discarded rather than resumed.
+    pub async fn run_cancellable(
+        &mut self,
+        token: CancellationToken,
+    ) -> Result<RunResult, VmError> {
+        self.run_until(async move {
+            token.cancelled().await;
+            Err(VmError::Cancelled)
+        })
+        .await
+    }
+
+    /// Runs like [`VM::run`] for at most `limit`, returning
+    /// [`RunResult::TimedOut`] once it passes, including while an opcode such
+    /// as `ReceiveMessage` is waiting.
+    ///
+    /// A run that times out between opcodes can be continued with another
+    /// call. As with [`VM::run_cancellable`], an opcode interrupted while
+    /// waiting has already advanced the instruction pointer and is not
+    /// retried.
+    pub async fn run_for(&mut self, limit: Duration) -> Result<RunResult, VmError> {
+        self.run_until(async move {
+            tokio::time::sleep(limit).await;
+            Ok(RunResult::TimedOut)
+        })
+        .await
+    }
+
+    /// How many messages the program has taken from the mailbox so far.
+    pub fn messages_received(&self) -> usize {
+        self.execution.messages_received
+    }
+
     /// Expose a reference to the execution stack for testing or inspection.
     pub fn stack(&self) -> &Vec<Value> {
         &self.execution.stack
     }
 
-    pub fn set_strategy(&mut self, _strategy: usize) {
-        log::info!("Set supervisor strategy to {}", _strategy);
+    /// Expose the heap for testing or inspection.
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    pub fn set_strategy(&mut self, strategy: usize) {
+        log::info!("[vm {}] Set supervisor strategy to {}", self.id, strategy);
+        self.strategy = strategy;
+    }
+
+    /// The strategy last given to [`VM::set_strategy`], `0` by default.
+    pub fn strategy(&self) -> usize {
+        self.strategy
     }
 
     pub fn restart_child(&mut self, _child_ref: usize) {
-        log::info!("Restarted child at {}", _child_ref);
+        log::info!("[vm {}] Restarted child at {}", self.id, _child_ref);
     }
 }
 
@@ -227,6 +783,30 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_dump_heap_lists_every_object() {
+        let (mut vm, _tx) = VM::new(vec![OpCode::SpawnActor(0)], None);
+        vm.run().await.unwrap();
+        let array = vm
+            .heap
+            .allocate(HeapObject::Array(vec![Value::Integer(1); 3], 2));
+        let string = vm
+            .heap
+            .allocate(HeapObject::String("a fairly long string value".into(), 0));
+
+        let dump = vm.dump_heap();
+        let lines: Vec<_> = dump.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0].split_whitespace().collect::<Vec<_>>(),
+            ["0", "rc=1", "Actor", "ip=0"]
+        );
+        assert!(lines[1].starts_with(&format!("{:>6}  rc=2", array)));
+        assert!(lines[1].ends_with("Array len=3"));
+        assert!(lines[2].starts_with(&format!("{:>6}  rc=0", string)));
+        assert!(lines[2].ends_with("String \"a fairly long st\"..."));
+    }
+
     #[tokio::test]
     async fn test_send_message_failure() {
         use crate::vm::error::VmError;
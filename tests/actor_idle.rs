@@ -0,0 +1,57 @@
+use raft::compiler::Compiler;
+use raft::runtime::Actor;
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+use raft::RunResult;
+
+#[tokio::test]
+async fn run_to_idle_processes_every_queued_message() {
+    let mut actor = Actor::new(Compiler::compile("ReceiveMessage 10 Mul").unwrap());
+    for n in 1..=3 {
+        actor.send(Value::Integer(n)).await.unwrap();
+    }
+
+    actor.run_to_idle().await.unwrap();
+    assert_eq!(
+        actor.vm().stack(),
+        &vec![Value::Integer(10), Value::Integer(20), Value::Integer(30)]
+    );
+}
+
+#[tokio::test]
+async fn run_to_idle_returns_immediately_on_an_empty_mailbox() {
+    let mut actor = Actor::new(Compiler::compile("ReceiveMessage").unwrap());
+
+    actor.run_to_idle().await.unwrap();
+    assert!(actor.vm().stack().is_empty());
+    assert_eq!(actor.vm().ip(), 0);
+}
+
+#[tokio::test]
+async fn run_to_idle_fails_when_a_pass_takes_no_message() {
+    let mut actor = Actor::new(Compiler::compile("1 Halt ReceiveMessage").unwrap());
+    actor.send(Value::Integer(1)).await.unwrap();
+
+    let err = actor.run_to_idle().await.unwrap_err();
+    assert!(matches!(err, VmError::MessageNotConsumed(1)));
+    assert_eq!(actor.vm().stack(), &vec![Value::Integer(1)]);
+}
+
+#[tokio::test]
+async fn run_to_idle_returns_a_yield_without_restarting_the_pass() {
+    let mut actor = Actor::new(Compiler::compile("ReceiveMessage Yield 2 Mul").unwrap());
+    actor.send(Value::Integer(3)).await.unwrap();
+    actor.send(Value::Integer(4)).await.unwrap();
+
+    assert_eq!(actor.run_to_idle().await.unwrap(), RunResult::Yielded);
+    assert_eq!(actor.vm().stack(), &vec![Value::Integer(3)]);
+    // Finishing the pass and going idle again handles the rest.
+    actor.run().await.unwrap();
+    assert_eq!(actor.run_to_idle().await.unwrap(), RunResult::Yielded);
+    actor.run().await.unwrap();
+    assert_eq!(actor.run_to_idle().await.unwrap(), RunResult::Completed);
+    assert_eq!(
+        actor.vm().stack(),
+        &vec![Value::Integer(6), Value::Integer(8)]
+    );
+}
@@ -0,0 +1,64 @@
+use raft::compiler::Compiler;
+use raft::runtime::{Actor, OnError};
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+
+/// Receives a divisor and leaves `10 / divisor` followed by a marker.
+fn divider() -> Actor {
+    Actor::new(Compiler::compile("ReceiveMessage 10 Swap Div 7").unwrap())
+}
+
+#[tokio::test]
+async fn stop_policy_propagates_the_fault() {
+    let mut actor = divider();
+    actor.send(Value::Integer(0)).await.unwrap();
+
+    let err = actor.run_supervised().await.expect_err("expected fault");
+    assert!(matches!(err, VmError::DivisionByZero));
+}
+
+#[tokio::test]
+async fn restart_policy_resets_and_processes_the_next_message() {
+    let mut actor = divider();
+    actor.set_policy(OnError::Restart);
+    actor.send(Value::Integer(0)).await.unwrap();
+    actor.send(Value::Integer(5)).await.unwrap();
+
+    actor.run_supervised().await.unwrap();
+    assert_eq!(
+        actor.vm().stack(),
+        &vec![Value::Integer(2), Value::Integer(7)]
+    );
+}
+
+#[tokio::test]
+async fn restart_policy_gives_up_after_max_restarts() {
+    let mut actor = divider();
+    actor.set_policy(OnError::Restart);
+    actor.set_max_restarts(1);
+    for _ in 0..3 {
+        actor.send(Value::Integer(0)).await.unwrap();
+    }
+
+    let err = actor.run_supervised().await.expect_err("expected fault");
+    assert!(matches!(err, VmError::DivisionByZero));
+}
+
+#[tokio::test]
+async fn ignore_policy_continues_after_the_fault() {
+    let mut actor = divider();
+    actor.set_policy(OnError::Ignore);
+    actor.send(Value::Integer(0)).await.unwrap();
+
+    actor.run_supervised().await.unwrap();
+    assert_eq!(actor.vm().stack(), &vec![Value::Integer(7)]);
+}
+
+#[tokio::test]
+async fn missing_bytecode_stops_regardless_of_policy() {
+    let mut actor = Actor::new(vec![]);
+    actor.set_policy(OnError::Restart);
+
+    let err = actor.run_supervised().await.expect_err("expected fault");
+    assert!(matches!(err, VmError::NoBytecode));
+}
@@ -0,0 +1,300 @@
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+use std::sync::atomic::Ordering;
+use tokio::sync::mpsc::channel;
+
+#[tokio::test]
+async fn spawned_actors_get_distinct_ids() {
+    let code = Compiler::compile("SpawnActor 2 SpawnActor 2 1").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    let ids: Vec<u64> = vm.stack()[..2]
+        .iter()
+        .map(|value| match value {
+            Value::Reference(address) => match vm.heap().get(*address) {
+                Some(HeapObject::Actor(actor, _, _)) => actor.id(),
+                other => panic!("Expected actor, got {other:?}"),
+            },
+            other => panic!("Expected actor reference, got {other:?}"),
+        })
+        .collect();
+    assert_ne!(ids[0], ids[1]);
+    assert!(ids.iter().all(|&id| id != vm.id()));
+}
+
+#[tokio::test]
+async fn detached_actor_runs_standalone() {
+    let code =
+        Compiler::compile("SpawnActor child Jump end check: IsValid Jump end child: 2 3 Add end:")
+            .unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    let Value::Reference(address) = vm.stack()[0] else {
+        panic!("Expected actor reference, got {:?}", vm.stack());
+    };
+
+    let (mut child, _sender) = vm.take_actor(address).expect("actor should detach");
+    assert!(vm.heap().get(address).is_none());
+    assert!(vm.take_actor(address).is_none());
+
+    child.run().await.unwrap();
+    assert_eq!(child.stack(), &vec![Value::Integer(5)]);
+
+    // Run `check:` against the parent's now-dangling reference.
+    vm.set_ip(2);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &vec![Value::Boolean(false)]);
+}
+
+#[tokio::test]
+async fn spawning_past_the_actor_limit_fails() {
+    let code = Compiler::compile("SpawnActor 0 SpawnSupervisor 0 SpawnActor 0").unwrap();
+    let (vm, _tx) = VM::new(code, None);
+    let mut vm = vm.with_max_actors(2);
+
+    let err = vm.run().await.expect_err("expected actor limit");
+    assert!(matches!(err, VmError::ActorLimitExceeded(2)));
+    assert_eq!(vm.stack().len(), 2);
+    assert_eq!(vm.heap().actor_count(), 2);
+
+    // Collecting an actor frees up its slot.
+    vm.pop_stack().unwrap();
+    vm.collect_garbage();
+    assert_eq!(vm.heap().actor_count(), 1);
+    vm.set_ip(2);
+    vm.run().await.unwrap();
+    assert_eq!(vm.heap().actor_count(), 2);
+}
+
+fn actor_sender(vm: &VM, value: Value) -> tokio::sync::mpsc::Sender<Value> {
+    match value {
+        Value::Reference(address) => match vm.heap().get(address) {
+            Some(HeapObject::Actor(_, sender, _) | HeapObject::Supervisor(_, sender, _)) => {
+                sender.clone()
+            }
+            other => panic!("Expected actor, got {other:?}"),
+        },
+        other => panic!("Expected actor reference, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn dropping_a_vm_closes_its_actors_mailboxes() {
+    let code = Compiler::compile("SpawnActor 0 SpawnSupervisor 0").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    let senders: Vec<_> = vm.stack().iter().map(|v| actor_sender(&vm, *v)).collect();
+    assert!(senders.iter().all(|sender| !sender.is_closed()));
+
+    drop(vm);
+    for sender in senders {
+        assert!(sender.is_closed());
+        assert!(sender.send(Value::Integer(1)).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn shutdown_stops_actors_but_keeps_the_vm() {
+    let code = Compiler::compile("SpawnActor 0 NewArray").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    let sender = actor_sender(&vm, vm.stack()[0]);
+
+    vm.shutdown();
+    assert!(sender.send(Value::Integer(1)).await.is_err());
+    assert_eq!(vm.heap().actor_count(), 0);
+    assert_eq!(vm.heap().len(), 1, "non-actor objects survive");
+}
+
+#[tokio::test]
+async fn actor_ip_reports_where_an_actor_is() {
+    // The child stops at the failing `Assert`, partway through its code.
+    let source = "SpawnActor child Dup ActorIp Swap Dup JoinActor Pop ActorIp \
+                  Jump end child: 1 false Assert 2 end:";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    // `child` is at 9; the failed `Assert` at 11 leaves the ip at 12.
+    assert_eq!(vm.stack(), &[Value::Integer(9), Value::Integer(12)]);
+}
+
+#[tokio::test]
+async fn actor_ip_rejects_non_actors() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("NewArray ActorIp").unwrap(), None);
+    assert!(matches!(
+        vm.run().await.unwrap_err(),
+        VmError::InvalidReference
+    ));
+}
+
+#[tokio::test]
+async fn supervisor_strategy_can_be_set_and_read_at_runtime() {
+    let source = "SpawnSupervisor sup Dup GetStrategy Swap 1 2 Add SetStrategyDyn GetStrategy \
+                  Jump end sup: Nop end:";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(0), Value::Integer(3)]);
+}
+
+#[tokio::test]
+async fn set_strategy_dyn_rejects_negative_strategies() {
+    let source = "SpawnSupervisor sup -1 SetStrategyDyn Jump end sup: Nop end:";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    assert!(matches!(
+        vm.run().await.unwrap_err(),
+        VmError::TypeMismatch("SetStrategyDyn")
+    ));
+}
+
+#[tokio::test]
+async fn send_if_alive_reports_delivery() {
+    let source = "1 SpawnActor child SendIfAlive Jump end child: ReceiveMessage end:";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Boolean(true)]);
+}
+
+#[tokio::test]
+async fn send_if_alive_to_a_stopped_actor_releases_the_message() {
+    let mut ctx = ExecutionContext::new(vec![OpCode::Return]);
+    let mut heap = Heap::new();
+    let (_tx, mut rx) = channel(1);
+    let array = heap.allocate(HeapObject::Array(vec![], 1));
+    ctx.stack.push(Value::Reference(array));
+    OpCode::SpawnActor(0)
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+    heap.shutdown();
+
+    OpCode::SendIfAlive
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+
+    assert_eq!(ctx.stack, vec![Value::Boolean(false)]);
+    assert_eq!(heap.get(array).map(HeapObject::ref_count), Some(0));
+}
+
+#[tokio::test]
+async fn drain_mailbox_collects_queued_messages_in_order() {
+    let bytecode = Compiler::compile("DrainMailbox DrainMailbox").unwrap();
+    let (mut vm, tx) = VM::new(bytecode, None);
+    for n in 1..=3 {
+        tx.send(Value::Integer(n)).await.unwrap();
+    }
+    vm.run().await.unwrap();
+
+    let [Value::Reference(first), Value::Reference(second)] = vm.stack()[..] else {
+        panic!("expected two arrays, got {:?}", vm.stack());
+    };
+    assert!(matches!(
+        vm.heap().get(first),
+        Some(HeapObject::Array(items, _))
+            if items == &[Value::Integer(1), Value::Integer(2), Value::Integer(3)]
+    ));
+    assert!(matches!(
+        vm.heap().get(second),
+        Some(HeapObject::Array(items, _)) if items.is_empty()
+    ));
+}
+
+#[tokio::test]
+async fn forked_actor_starts_from_the_parent_state() {
+    let source = "1 NewArray StoreVar 0 2 Fork child Halt child: 3";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+
+    let [Value::Integer(1), Value::Integer(2), Value::Reference(actor)] = vm.stack()[..] else {
+        panic!("expected the stack and the child, got {:?}", vm.stack());
+    };
+    let (mut child, _child_tx) = vm.take_actor(actor).unwrap();
+    assert_eq!(child.stack(), &[Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(child.ip(), 6);
+    // The local's array was copied into the child's heap.
+    assert!(matches!(child.heap().get(0), Some(HeapObject::Array(..))));
+
+    child.run().await.unwrap();
+    assert_eq!(
+        child.stack(),
+        &[Value::Integer(1), Value::Integer(2), Value::Integer(3)]
+    );
+}
+
+#[tokio::test]
+async fn detached_actors_run_in_the_background() {
+    let source = "NewCounter StoreVar 0 SpawnDetached child LoadVar 0 Halt \
+                  child: LoadVar 0 CounterInc";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    // Nothing but the counter is left behind.
+    let [Value::Reference(address)] = vm.stack()[..] else {
+        panic!("expected only the counter, got {:?}", vm.stack());
+    };
+    let Some(HeapObject::Counter(count, _)) = vm.heap().get(address) else {
+        panic!("expected a counter at {}", address);
+    };
+
+    for _ in 0..100 {
+        if count.load(Ordering::SeqCst) == 1 {
+            break;
+        }
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn spawned_actors_inherit_the_actor_limit() {
+    for spawn in ["SpawnActor", "Fork"] {
+        let source = format!("{spawn} child Halt child: SpawnActor end SpawnActor end end: Halt");
+        let (vm, _tx) = VM::new(Compiler::compile(&source).unwrap(), None);
+        let mut vm = vm.with_max_actors(1);
+        vm.run().await.unwrap();
+        let [Value::Reference(address)] = vm.stack()[..] else {
+            panic!("expected the child, got {:?}", vm.stack());
+        };
+
+        let (mut child, _child_tx) = vm.take_actor(address).unwrap();
+        let err = child.run().await.expect_err("expected actor limit");
+        assert!(matches!(err, VmError::ActorLimitExceeded(1)), "{spawn}");
+        assert_eq!(child.heap().actor_count(), 1, "{spawn}");
+    }
+}
+
+#[tokio::test]
+async fn detaching_past_the_actor_limit_fails() {
+    let code = Compiler::compile("SpawnActor 2 SpawnDetached 2 Halt").unwrap();
+    let (vm, _tx) = VM::new(code, None);
+    let mut vm = vm.with_max_actors(1);
+
+    let err = vm.run().await.expect_err("expected actor limit");
+    assert!(matches!(err, VmError::ActorLimitExceeded(1)));
+}
+
+#[tokio::test]
+async fn detached_actors_inherit_the_actor_limit() {
+    // The child counts once, then stops at its second spawn.
+    let source = "NewCounter StoreVar 0 SpawnDetached child LoadVar 0 Halt \
+                  child: LoadVar 0 CounterInc SpawnActor end SpawnActor end \
+                  LoadVar 0 CounterInc end: Halt";
+    let (vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    let mut vm = vm.with_max_actors(1);
+    vm.run().await.unwrap();
+    let [Value::Reference(address)] = vm.stack()[..] else {
+        panic!("expected only the counter, got {:?}", vm.stack());
+    };
+    let Some(HeapObject::Counter(count, _)) = vm.heap().get(address) else {
+        panic!("expected a counter at {}", address);
+    };
+
+    for _ in 0..100 {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
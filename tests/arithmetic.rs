@@ -0,0 +1,83 @@
+mod common;
+
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+
+async fn eval(source: &str) -> Result<Vec<Value>, VmError> {
+    let (vm, result) = common::run(source).await;
+    result.map(|_| vm.stack().clone())
+}
+
+#[tokio::test]
+async fn exp_raises_integers() {
+    assert_eq!(eval("2 10 Exp").await.unwrap(), vec![Value::Integer(1024)]);
+}
+
+#[tokio::test]
+async fn exp_reports_integer_overflow() {
+    let err = eval("2 40 Exp").await.expect_err("expected overflow");
+    assert!(matches!(err, VmError::IntegerOverflow("Exp")));
+}
+
+#[tokio::test]
+async fn exp_with_negative_exponent_yields_a_float() {
+    assert_eq!(eval("2 -2 Exp").await.unwrap(), vec![Value::Float(0.25)]);
+}
+
+#[tokio::test]
+async fn shifts_move_bits() {
+    assert_eq!(eval("1 4 Shl").await.unwrap(), vec![Value::Integer(16)]);
+    assert_eq!(eval("-16 2 Shr").await.unwrap(), vec![Value::Integer(-4)]);
+}
+
+#[tokio::test]
+async fn shifts_of_32_or_more_saturate() {
+    assert_eq!(eval("1 35 Shl").await.unwrap(), vec![Value::Integer(0)]);
+    assert_eq!(eval("7 32 Shr").await.unwrap(), vec![Value::Integer(0)]);
+    assert_eq!(eval("-7 40 Shr").await.unwrap(), vec![Value::Integer(-1)]);
+}
+
+#[tokio::test]
+async fn negative_shift_counts_are_errors() {
+    let err = eval("1 -1 Shl").await.expect_err("expected negative shift");
+    assert!(matches!(err, VmError::NegativeShift(-1)));
+    let err = eval("1 -1 Shr").await.expect_err("expected negative shift");
+    assert!(matches!(err, VmError::NegativeShift(-1)));
+}
+
+#[tokio::test]
+async fn safe_div_pushes_null_on_division_by_zero() {
+    assert_eq!(eval("4 0 SafeDiv").await.unwrap(), vec![Value::Null]);
+    assert_eq!(eval("4.0 0.0 SafeDiv").await.unwrap(), vec![Value::Null]);
+}
+
+#[tokio::test]
+async fn safe_div_divides_like_div() {
+    assert_eq!(eval("4 2 SafeDiv").await.unwrap(), vec![Value::Integer(2)]);
+    let err = eval("4 true SafeDiv").await.expect_err("expected mismatch");
+    assert!(matches!(err, VmError::TypeMismatch("SafeDiv")));
+}
+
+#[tokio::test]
+async fn float_eq_compares_within_tolerance() {
+    assert_eq!(
+        eval("0.1 0.2 Add 0.3 0.000001 FloatEq").await.unwrap(),
+        vec![Value::Boolean(true)]
+    );
+    assert_eq!(
+        eval("0.1 0.2 Add 0.3 0.0 FloatEq").await.unwrap(),
+        vec![Value::Boolean(false)]
+    );
+    assert_eq!(
+        eval("1 1.05 0.1 FloatEq").await.unwrap(),
+        vec![Value::Boolean(true)]
+    );
+}
+
+#[tokio::test]
+async fn float_eq_rejects_non_numbers() {
+    let err = eval("1.0 true 0.1 FloatEq")
+        .await
+        .expect_err("expected mismatch");
+    assert!(matches!(err, VmError::TypeMismatch("FloatEq")));
+}
@@ -0,0 +1,305 @@
+use raft::compiler::{Compiler, CompilerError};
+use raft::vm::error::VmError;
+use raft::vm::heap::HeapObject;
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+fn array_contents(vm: &VM, value: Value) -> (Vec<Value>, usize) {
+    match value {
+        Value::Reference(addr) => match vm.heap().get(addr) {
+            Some(HeapObject::Array(items, rc)) => (items.clone(), *rc),
+            other => panic!("Expected array at {addr}, got {other:?}"),
+        },
+        other => panic!("Expected array reference, got {other:?}"),
+    }
+}
+
+#[test]
+fn array_literal_compiles_to_construction_code() {
+    let bytecode = Compiler::compile("[1, 2,3]").unwrap();
+    assert_eq!(bytecode.len(), 7);
+    assert!(matches!(bytecode[0], OpCode::NewArray));
+    assert!(matches!(bytecode[1], OpCode::PushConst(Value::Integer(1))));
+    assert!(matches!(bytecode[2], OpCode::ArrayPush));
+    assert!(matches!(bytecode[5], OpCode::PushConst(Value::Integer(3))));
+    assert!(matches!(bytecode[6], OpCode::ArrayPush));
+}
+
+#[tokio::test]
+async fn array_literal_produces_heap_array() {
+    let code = Compiler::compile("[1, 2, 3]").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack().len(), 1);
+    let (items, rc) = array_contents(&vm, vm.stack()[0]);
+    assert_eq!(items, [1, 2, 3].map(Value::Integer).to_vec());
+    assert_eq!(rc, 1);
+}
+
+#[tokio::test]
+async fn nested_array_literal_is_owned_by_its_parent() {
+    let code = Compiler::compile("[true, [2.5], []]").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    let (outer, _) = array_contents(&vm, vm.stack()[0]);
+    assert_eq!(outer.len(), 3);
+    assert_eq!(outer[0], Value::Boolean(true));
+    let (inner, inner_rc) = array_contents(&vm, outer[1]);
+    assert_eq!(inner, vec![Value::Float(2.5)]);
+    assert_eq!(inner_rc, 1);
+    assert!(array_contents(&vm, outer[2]).0.is_empty());
+}
+
+#[test]
+fn malformed_array_literals_are_rejected() {
+    let err = Compiler::compile("[1, 2").unwrap_err();
+    assert!(matches!(err, CompilerError::ParseError(m) if m.contains("Unterminated")));
+
+    let err = Compiler::compile("[1, Pop]").unwrap_err();
+    assert!(matches!(err, CompilerError::ParseError(m) if m.contains("Pop")));
+}
+
+#[tokio::test]
+async fn array_slice_copies_the_subrange() {
+    let code = Compiler::compile("[10, 20, 30, 40] 1 3 ArraySlice").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack().len(), 1);
+    let (items, rc) = array_contents(&vm, vm.stack()[0]);
+    assert_eq!(items, [20, 30].map(Value::Integer).to_vec());
+    assert_eq!(rc, 1);
+}
+
+#[tokio::test]
+async fn array_slice_shares_reference_elements() {
+    let code = Compiler::compile("[[1], [2]] Dup 0 1 ArraySlice").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    let (slice, _) = array_contents(&vm, vm.stack()[1]);
+    assert_eq!(slice.len(), 1);
+    let (_, shared_rc) = array_contents(&vm, slice[0]);
+    assert_eq!(shared_rc, 2, "owned by both the original and the slice");
+}
+
+#[tokio::test]
+async fn array_slice_rejects_bad_ranges() {
+    for range in ["0 5", "3 1", "-1 2"] {
+        let code = Compiler::compile(&format!("[10, 20, 30, 40] {range} ArraySlice")).unwrap();
+        let (mut vm, _tx) = VM::new(code, None);
+        let err = vm.run().await.expect_err("expected out of bounds");
+        assert!(matches!(err, VmError::IndexOutOfBounds), "{range}");
+    }
+}
+
+#[tokio::test]
+async fn array_map_calls_the_subroutine_per_element() {
+    let code =
+        Compiler::compile("[1, 2, 3] ArrayMap double Jump end double: 2 Mul Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack().len(), 1);
+    let (items, rc) = array_contents(&vm, vm.stack()[0]);
+    assert_eq!(items, [2, 4, 6].map(Value::Integer).to_vec());
+    assert_eq!(rc, 1);
+}
+
+#[tokio::test]
+async fn array_map_collects_returned_references() {
+    let code = Compiler::compile(
+        "[1, 2] ArrayMap wrap Jump end wrap: NewArray Swap ArrayPush Return end:",
+    )
+    .unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    let (items, _) = array_contents(&vm, vm.stack()[0]);
+    let (inner, rc) = array_contents(&vm, items[1]);
+    assert_eq!(inner, vec![Value::Integer(2)]);
+    assert_eq!(rc, 1);
+}
+
+#[tokio::test]
+async fn array_map_rejects_an_unbalanced_subroutine() {
+    let code = Compiler::compile("[1, 2] ArrayMap bad Jump end bad: Dup Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected unbalanced stack");
+    assert!(matches!(err, VmError::UnbalancedSubroutine(7)));
+}
+
+#[tokio::test]
+async fn halting_inside_a_callback_fails_the_calling_opcode() {
+    let code = Compiler::compile("[1, 2] ArrayMap stop Jump end stop: Halt Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm
+        .run()
+        .await
+        .expect_err("expected a stop inside the callback");
+    assert!(matches!(err, VmError::StoppedInSubroutine(7)));
+    assert!(vm.snapshot().call_stack.is_empty());
+    assert_eq!(vm.ip(), 6);
+
+    let code =
+        Compiler::compile("[1, 2] ArrayFilter stop Jump end stop: Yield Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm
+        .run()
+        .await
+        .expect_err("expected a stop inside the callback");
+    assert!(matches!(err, VmError::StoppedInSubroutine(7)));
+}
+
+#[tokio::test]
+async fn array_filter_keeps_elements_matching_the_predicate() {
+    let code = Compiler::compile(
+        "[1, 2, 3, 4] ArrayFilter odd Jump end \
+         odd: 2 Mod Switch 2 even yes even: false Return yes: true Return end:",
+    )
+    .unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack().len(), 1);
+    let (items, rc) = array_contents(&vm, vm.stack()[0]);
+    assert_eq!(items, [1, 3].map(Value::Integer).to_vec());
+    assert_eq!(rc, 1);
+}
+
+#[tokio::test]
+async fn array_filter_only_shares_retained_references() {
+    // The predicate keeps only the first element it sees.
+    let code = Compiler::compile(
+        "0 StoreVar 0 [[1], [2]] Dup ArrayFilter first Jump end \
+         first: Pop LoadVar 0 Dup 1 Add StoreVar 0 Switch 1 keep false Return keep: true Return end:",
+    )
+    .unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    let (original, _) = array_contents(&vm, vm.stack()[0]);
+    let (filtered, _) = array_contents(&vm, vm.stack()[1]);
+    assert_eq!(filtered, vec![original[0]]);
+    assert_eq!(array_contents(&vm, original[0]).1, 2);
+    assert_eq!(array_contents(&vm, original[1]).1, 1);
+}
+
+#[tokio::test]
+async fn array_filter_requires_a_boolean_predicate() {
+    let code = Compiler::compile("[1] ArrayFilter id Jump end id: Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected type mismatch");
+    assert!(matches!(err, VmError::TypeMismatch("ArrayFilter")));
+}
+
+#[tokio::test]
+async fn array_reduce_threads_the_accumulator() {
+    let code =
+        Compiler::compile("[1, 2, 3, 4] 0 ArrayReduce add Jump end add: Add Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(10)]);
+}
+
+#[tokio::test]
+async fn array_reduce_passes_the_accumulator_first() {
+    let code =
+        Compiler::compile("[1, 2, 3] 10 ArrayReduce sub Jump end sub: Sub Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(4)]);
+}
+
+#[tokio::test]
+async fn array_reduce_of_an_empty_array_is_the_initial_value() {
+    let code = Compiler::compile("[] 7 ArrayReduce add Jump end add: Add Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(7)]);
+}
+
+#[tokio::test]
+async fn array_reduce_rejects_an_unbalanced_subroutine() {
+    let code = Compiler::compile("[1] 0 ArrayReduce bad Jump end bad: Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected unbalanced stack");
+    assert!(matches!(err, VmError::UnbalancedSubroutine(_)));
+}
+
+#[tokio::test]
+async fn collect_gathers_the_top_values_in_order() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("0 1 2 3 Collect 3").unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack().len(), 2);
+    assert_eq!(vm.stack()[0], Value::Integer(0));
+    let (items, rc) = array_contents(&vm, vm.stack()[1]);
+    assert_eq!(
+        items,
+        vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)]
+    );
+    assert_eq!(rc, 1);
+}
+
+#[tokio::test]
+async fn collect_moves_reference_counts_into_the_array() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("NewArray Collect 1").unwrap(), None);
+    vm.run().await.unwrap();
+    let (items, _) = array_contents(&vm, vm.stack()[0]);
+    let (_, inner_rc) = array_contents(&vm, items[0]);
+    assert_eq!(inner_rc, 1);
+}
+
+#[tokio::test]
+async fn collect_underflows_on_a_short_stack() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("1 2 Collect 3").unwrap(), None);
+    assert!(matches!(
+        vm.run().await.unwrap_err(),
+        VmError::StackUnderflow
+    ));
+}
+
+#[tokio::test]
+async fn spread_pushes_array_elements_in_order() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("[1, 2, 3] Spread").unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(
+        vm.stack(),
+        &[Value::Integer(1), Value::Integer(2), Value::Integer(3)]
+    );
+    assert_eq!(vm.heap_ref_count(0), Some(0), "spread consumes the array");
+}
+
+#[tokio::test]
+async fn spread_gives_the_stack_its_own_counts() {
+    let (mut vm, _tx) = VM::new(
+        Compiler::compile("NewArray Collect 1 Spread").unwrap(),
+        None,
+    );
+    vm.run().await.unwrap();
+    let (_, rc) = array_contents(&vm, vm.stack()[0]);
+    // One count from the stack, one from the consumed outer array.
+    assert_eq!(rc, 2);
+}
+
+#[tokio::test]
+async fn unbox_takes_the_only_element() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("[42] Unbox \"A\" Unbox").unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(42), Value::Integer(65)]);
+    assert_eq!(vm.heap_ref_count(0), Some(0), "the array is released");
+}
+
+#[tokio::test]
+async fn unbox_rejects_other_shapes() {
+    for source in ["[1, 2] Unbox", "[] Unbox", "\"ab\" Unbox", "7 Unbox"] {
+        let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+        assert!(
+            matches!(vm.run().await, Err(VmError::TypeMismatch("Unbox"))),
+            "{source}"
+        );
+    }
+}
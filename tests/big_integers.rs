@@ -0,0 +1,61 @@
+mod common;
+
+use common::run;
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::heap::HeapObject;
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+fn big_text(vm: &VM, value: Value) -> String {
+    match value {
+        Value::Reference(address) => match vm.heap().get(address) {
+            Some(HeapObject::BigInt(value, _)) => value.to_string(),
+            other => panic!("Expected big integer at {address}, got {other:?}"),
+        },
+        other => panic!("Expected big integer reference, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn overflowing_multiplication_produces_a_big_integer() {
+    let (vm, result) = run("2147483647 2147483647 Mul").await;
+    result.unwrap();
+    assert_eq!(big_text(&vm, vm.stack()[0]), "4611686014132420609");
+}
+
+#[tokio::test]
+async fn big_multiplication_beyond_64_bits() {
+    let (vm, result) = run("9223372036854775807 9223372036854775807 Mul").await;
+    result.unwrap();
+    assert_eq!(
+        big_text(&vm, vm.stack()[0]),
+        "85070591730234615847396907784232501249"
+    );
+}
+
+#[test]
+fn large_literals_compile_to_big_integers() {
+    let bytecode = Compiler::compile("-99999999999").unwrap();
+    match bytecode[0] {
+        OpCode::PushBigInt(digits) => assert_eq!(&*digits.as_str(), "-99999999999"),
+        other => panic!("Expected PushBigInt, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn small_results_return_to_plain_integers() {
+    let (vm, result) = run("99999999999 99999999998 Sub").await;
+    result.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(1)]);
+    // Both operands were released.
+    assert!(vm.heap().iter().all(|(_, object)| object.ref_count() == 0));
+}
+
+#[tokio::test]
+async fn big_integers_reject_non_integer_operands() {
+    let (_, result) = run("99999999999 1.5 Add").await;
+    let err = result.unwrap_err();
+    assert!(matches!(err, VmError::TypeMismatch("Add")));
+}
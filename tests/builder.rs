@@ -0,0 +1,64 @@
+use raft::compiler::Compiler;
+use raft::vm::{Value, VmBuilder, VmError, VM};
+
+#[tokio::test]
+async fn builder_applies_its_options() {
+    let bytecode = Compiler::compile("99 Now 1 2").unwrap();
+    let (mut vm, tx) = VmBuilder::new()
+        .mailbox_capacity(4)
+        .stack_capacity(2)
+        .heap_capacity(64)
+        .clock(|| 1234)
+        .entry(1)
+        .build(bytecode);
+
+    assert_eq!(tx.max_capacity(), 4);
+    assert!(vm.heap().capacity() >= 64);
+    assert!(matches!(vm.run().await, Err(VmError::StackOverflow)));
+    assert_eq!(vm.stack(), &[Value::Integer(1234), Value::Integer(1)]);
+}
+
+#[tokio::test]
+async fn builder_seed_matches_set_seed() {
+    let bytecode = Compiler::compile("Rand Rand").unwrap();
+    let (mut built, _tx) = VmBuilder::new().seed(7).build(bytecode.clone());
+    let (mut seeded, _tx) = VM::new(bytecode, None);
+    seeded.set_seed(7);
+
+    built.run().await.unwrap();
+    seeded.run().await.unwrap();
+    assert_eq!(built.stack(), seeded.stack());
+}
+
+#[tokio::test]
+async fn builder_limits_actors() {
+    let bytecode = Compiler::compile("SpawnActor 0 SpawnActor 0").unwrap();
+    let (mut vm, _tx) = VmBuilder::new().max_actors(1).build(bytecode);
+    assert!(matches!(
+        vm.run().await,
+        Err(VmError::ActorLimitExceeded(1))
+    ));
+}
+
+#[tokio::test]
+async fn builder_defaults_locals_to_null() {
+    let bytecode = Compiler::compile("LoadVar 3").unwrap();
+    let (mut vm, _tx) = VmBuilder::new()
+        .default_locals_to_null(true)
+        .build(bytecode);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Null]);
+}
+
+#[test]
+fn default_builder_matches_new() {
+    let (_vm, tx) = VmBuilder::new().build(vec![]);
+    let (_vm, default_tx) = VM::new(vec![], None);
+    assert_eq!(tx.max_capacity(), default_tx.max_capacity());
+}
+
+#[test]
+#[should_panic(expected = "mailbox capacity must be greater than zero")]
+fn zero_mailbox_capacity_panics_in_the_setter() {
+    let _ = VmBuilder::new().mailbox_capacity(0);
+}
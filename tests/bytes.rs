@@ -0,0 +1,44 @@
+mod common;
+
+use common::run;
+use raft::vm::error::VmError;
+use raft::vm::heap::HeapObject;
+use raft::vm::value::Value;
+
+#[tokio::test]
+async fn byte_buffer_reads_and_writes_indices() {
+    let (vm, result) =
+        run("4 NewBytes 1 255 BytesSet 3 7 BytesSet Dup 1 BytesGet Swap Dup BytesLen").await;
+    result.unwrap();
+
+    assert_eq!(vm.stack()[0], Value::Integer(255));
+    assert_eq!(vm.stack()[2], Value::Integer(4));
+    let Value::Reference(address) = vm.stack()[1] else {
+        panic!("Expected bytes reference, got {:?}", vm.stack());
+    };
+    match vm.heap().get(address) {
+        Some(HeapObject::Bytes(bytes, rc)) => {
+            assert_eq!(bytes, &[0, 255, 0, 7]);
+            assert_eq!(*rc, 1);
+        }
+        other => panic!("Expected bytes, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn byte_access_outside_the_buffer_is_out_of_bounds() {
+    for source in [
+        "2 NewBytes 2 BytesGet",
+        "2 NewBytes -1 BytesGet",
+        "2 NewBytes 5 1 BytesSet",
+    ] {
+        let (_, result) = run(source).await;
+        assert!(matches!(result, Err(VmError::IndexOutOfBounds)), "{source}");
+    }
+}
+
+#[tokio::test]
+async fn bytes_set_rejects_values_that_are_not_bytes() {
+    let (_, result) = run("1 NewBytes 0 256 BytesSet").await;
+    assert!(matches!(result, Err(VmError::ByteOutOfRange(256))));
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use raft::vm::error::VmError;
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn cancelling_interrupts_a_blocked_receive() {
+    let (mut vm, _tx) = VM::new(vec![OpCode::ReceiveMessage], None);
+    let token = CancellationToken::new();
+
+    let canceller = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        canceller.cancel();
+    });
+
+    let err = tokio::time::timeout(Duration::from_secs(5), vm.run_cancellable(token))
+        .await
+        .expect("cancellation should unblock the VM")
+        .expect_err("expected cancellation");
+    assert!(matches!(err, VmError::Cancelled));
+}
+
+#[tokio::test]
+async fn uncancelled_run_completes_normally() {
+    let code = vec![
+        OpCode::PushConst(Value::Integer(2)),
+        OpCode::PushConst(Value::Integer(3)),
+        OpCode::Add,
+    ];
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run_cancellable(CancellationToken::new()).await.unwrap();
+    assert_eq!(vm.stack(), &vec![Value::Integer(5)]);
+}
+
+#[tokio::test]
+async fn already_cancelled_token_stops_before_first_step() {
+    let (mut vm, _tx) = VM::new(vec![OpCode::PushConst(Value::Integer(1))], None);
+    let token = CancellationToken::new();
+    token.cancel();
+    let err = vm
+        .run_cancellable(token)
+        .await
+        .expect_err("expected cancellation");
+    assert!(matches!(err, VmError::Cancelled));
+    assert!(vm.stack().is_empty());
+}
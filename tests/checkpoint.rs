@@ -0,0 +1,72 @@
+use raft::compiler::Compiler;
+use raft::vm::heap::HeapObject;
+use raft::vm::value::Value;
+use raft::vm::{RunResult, VmSnapshot, VM};
+use std::sync::{Arc, Mutex};
+
+#[tokio::test]
+async fn checkpoint_fires_every_n_steps() {
+    // Ten instructions, so an interval of three fires after steps 3, 6, and 9.
+    let code = Compiler::compile("1 2 Add 3 Mul 4 Sub 5 Add Pop").unwrap();
+    assert_eq!(code.len(), 10);
+    let (mut vm, _tx) = VM::new(code, None);
+    let ips = Arc::new(Mutex::new(Vec::new()));
+    let seen = ips.clone();
+    vm.set_checkpoint(
+        3,
+        Box::new(move |snapshot: &VmSnapshot| seen.lock().unwrap().push(snapshot.ip)),
+    );
+
+    vm.run().await.unwrap();
+    assert_eq!(*ips.lock().unwrap(), vec![3, 6, 9]);
+}
+
+#[tokio::test]
+async fn checkpoint_snapshot_resumes_to_the_same_result() {
+    let source = "[1, 2] StoreVar 0 LoadVar 0 3 ArrayPush 10 20 Add";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    let saved = Arc::new(Mutex::new(None));
+    let slot = saved.clone();
+    vm.set_checkpoint(
+        7,
+        Box::new(move |snapshot: &VmSnapshot| {
+            slot.lock().unwrap().get_or_insert_with(|| snapshot.clone());
+        }),
+    );
+    vm.run().await.unwrap();
+    let expected = vm.stack().clone();
+
+    // Resume a brand new VM from the first checkpoint.
+    let snapshot = saved.lock().unwrap().take().expect("checkpoint fired");
+    assert_eq!(snapshot.ip, 7);
+    let (mut resumed, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    resumed.restore(&snapshot);
+    resumed.run().await.unwrap();
+
+    assert_eq!(resumed.stack(), &expected);
+    assert_eq!(expected[1], Value::Integer(30));
+    assert_eq!(
+        format!("{:?}", resumed.heap().get(0)),
+        format!("{:?}", vm.heap().get(0))
+    );
+}
+
+#[tokio::test]
+async fn restore_keeps_objects_a_snapshot_cannot_copy() {
+    let code = Compiler::compile(
+        "NewChannel StoreVar 0 [1] StoreVar 1 Yield \
+         7 LoadVar 0 ChannelSend LoadVar 0 ChannelRecv",
+    )
+    .unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    assert_eq!(vm.run().await.unwrap(), RunResult::Yielded);
+
+    let snapshot = vm.snapshot();
+    vm.restore(&snapshot);
+    assert!(matches!(vm.heap().get(0), Some(HeapObject::Channel(..))));
+    assert!(matches!(vm.heap().get(1), Some(HeapObject::Array(..))));
+
+    // The channel still works after the restore.
+    assert_eq!(vm.run().await.unwrap(), RunResult::Completed);
+    assert_eq!(vm.stack(), &[Value::Integer(7)]);
+}
@@ -0,0 +1,30 @@
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+#[tokio::test]
+async fn now_reads_the_injected_clock() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("Now").unwrap(), None);
+    vm.set_clock(|| 1_500);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(1_500)]);
+}
+
+#[tokio::test]
+async fn default_clock_starts_near_zero() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("Now").unwrap(), None);
+    vm.run().await.unwrap();
+    let [Value::Integer(millis)] = vm.stack()[..] else {
+        panic!("expected one integer, got {:?}", vm.stack());
+    };
+    assert!((0..60_000).contains(&millis));
+}
+
+#[tokio::test]
+async fn now_reports_readings_that_do_not_fit() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("Now").unwrap(), None);
+    vm.set_clock(|| u64::MAX);
+    let err = vm.run().await.unwrap_err();
+    assert!(matches!(err, VmError::IntegerOverflow("Now")));
+}
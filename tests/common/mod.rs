@@ -0,0 +1,43 @@
+//! Helpers shared by the integration tests.
+
+// Each test crate compiles its own copy of this module and uses only some
+// of the helpers.
+#![allow(dead_code)]
+
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::opcodes::OpCode;
+use raft::{RunResult, VM};
+use tokio::sync::mpsc::channel;
+
+/// Compiles `source`, runs it on a fresh VM, and hands back the VM together
+/// with how the run ended.
+pub async fn run(source: &str) -> (VM, Result<RunResult, VmError>) {
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    let result = vm.run().await;
+    (vm, result)
+}
+
+/// A fresh VM for `source`, not yet run. Its mailbox has no senders left.
+pub fn vm(source: &str) -> VM {
+    VM::new(Compiler::compile(source).unwrap(), None).0
+}
+
+/// Executes `code` one opcode at a time against `ctx` and `heap`, failing
+/// the test if any opcode does.
+pub async fn execute(ctx: &mut ExecutionContext, heap: &mut Heap, code: &[OpCode]) {
+    let (_tx, mut rx) = channel(1);
+    for opcode in code {
+        opcode.execute(ctx, heap, &mut rx).await.unwrap();
+    }
+}
+
+/// The reference count of the actor at `address`.
+pub fn actor_ref_count(heap: &Heap, address: usize) -> usize {
+    match heap.get(address) {
+        Some(HeapObject::Actor(_, _, rc)) => *rc,
+        _ => panic!("Expected actor at address {address}"),
+    }
+}
@@ -0,0 +1,33 @@
+use raft::compiler::{CompileCache, CompilerError};
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+
+#[test]
+fn repeated_source_hits_the_cache() {
+    let mut cache = CompileCache::new();
+    let first = cache.get_or_compile("1 2 Add").unwrap();
+    let second = cache.get_or_compile("1 2 Add").unwrap();
+
+    assert_eq!(format!("{first:?}"), format!("{second:?}"));
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn different_source_misses() {
+    let mut cache = CompileCache::new();
+    cache.get_or_compile("1 2 Add").unwrap();
+    let other = cache.get_or_compile("3").unwrap();
+
+    assert!(matches!(other[..], [OpCode::PushConst(Value::Integer(3))]));
+    assert_eq!((cache.hits(), cache.misses()), (0, 2));
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn compile_errors_are_not_cached() {
+    let mut cache = CompileCache::new();
+    let err = cache.get_or_compile("bogus").unwrap_err();
+    assert!(matches!(err, CompilerError::InvalidToken(_)));
+    assert!(cache.is_empty());
+}
@@ -1,8 +1,8 @@
-use raft::compiler::{Compiler, CompilerError};
+use raft::compiler::{Compiler, CompilerError, CompilerOptions, CompilerWarning};
 use raft::run;
 use raft::vm::opcodes::OpCode;
 use raft::vm::value::Value;
-use raft::vm::VmError;
+use raft::vm::{VmError, VM};
 
 #[test]
 fn compile_arithmetic_tokens() {
@@ -35,6 +35,7 @@ fn compile_control_flow_tokens() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn compile_float_tokens() {
     let source = "3.14 2.0 +";
     let bytecode = Compiler::compile(source).unwrap();
@@ -97,6 +98,25 @@ fn variable_tokens_reject_invalid_operands() {
     ));
 }
 
+#[test]
+fn strict_mode_rejects_load_before_store() {
+    let err = Compiler::compile_strict("LoadVar 0 1 StoreVar 0").unwrap_err();
+    assert!(matches!(err, CompilerError::UndefinedVariable(0)));
+
+    // The default mode defers the failure to runtime.
+    assert!(Compiler::compile("LoadVar 0 1 StoreVar 0").is_ok());
+}
+
+#[test]
+fn strict_mode_accepts_load_after_store() {
+    let bytecode = Compiler::compile_strict("1 StoreVar 0 LoadVar 0").unwrap();
+    assert_eq!(bytecode.len(), 3);
+    assert!(matches!(bytecode[2], OpCode::LoadVar(0)));
+
+    let err = Compiler::compile_strict("1 StoreVar 0 LoadVar 1").unwrap_err();
+    assert!(matches!(err, CompilerError::UndefinedVariable(1)));
+}
+
 #[test]
 fn compile_actor_and_supervisor_tokens() {
     let source =
@@ -111,6 +131,89 @@ fn compile_actor_and_supervisor_tokens() {
     assert!(matches!(bytecode[5], OpCode::RestartChild(1)));
 }
 
+#[test]
+fn streaming_compilation_matches_compile() {
+    let source = "5 3 - StoreVar 0 LoadVar 0 Dup Swap Pop true 2.5 + \
+                  1 JumpIfFalse 4 Call 6 Jump 8 Return SpawnActor 0 SendMessage \
+                  ReceiveMessage SpawnSupervisor 2 SetStrategy 1 RestartChild 0";
+
+    let expected = Compiler::compile(source).unwrap();
+    let streamed = Compiler::compile_streaming(source)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(format!("{streamed:?}"), format!("{expected:?}"));
+}
+
+#[test]
+fn streaming_compilation_stops_after_first_error() {
+    let mut stream = Compiler::compile_streaming("1 Jump bogus 2");
+    assert!(matches!(
+        stream.next(),
+        Some(Ok(OpCode::PushConst(Value::Integer(1))))
+    ));
+    assert!(matches!(
+        stream.next(),
+        Some(Err(CompilerError::InvalidAddress(a))) if a == "bogus"
+    ));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn compile_assert_with_optional_message() {
+    let bytecode = Compiler::compile("true Assert false Assert \"stack is  empty\" 1").unwrap();
+    assert_eq!(bytecode.len(), 5);
+    assert!(matches!(bytecode[1], OpCode::Assert(None)));
+    match bytecode[3] {
+        OpCode::Assert(Some(message)) => assert_eq!(&*message.as_str(), "stack is  empty"),
+        other => panic!("Expected Assert with message, got {other:?}"),
+    }
+    assert!(matches!(bytecode[4], OpCode::PushConst(Value::Integer(1))));
+
+    let err = Compiler::compile("false Assert \"oops").unwrap_err();
+    assert!(matches!(err, CompilerError::ParseError(m) if m.contains("oops")));
+}
+
+#[test]
+fn labels_resolve_forward_and_backward_references() {
+    let bytecode =
+        Compiler::compile("start: true JumpIfFalse done Jump start done: Return").unwrap();
+    assert_eq!(bytecode.len(), 4);
+    assert!(matches!(bytecode[1], OpCode::JumpIfFalse(3)));
+    assert!(matches!(bytecode[2], OpCode::Jump(0)));
+
+    let err = Compiler::compile("a: a: Return").unwrap_err();
+    assert!(matches!(err, CompilerError::DuplicateLabel(l) if l == "a"));
+}
+
+#[test]
+fn align_pads_with_nops_to_the_next_multiple() {
+    let bytecode = Compiler::compile("1 align 4 target: 2 align 4 Jump target").unwrap();
+    assert_eq!(bytecode.len(), 9);
+    assert!(bytecode[1..4].iter().all(|op| matches!(op, OpCode::Nop)));
+    assert!(matches!(bytecode[4], OpCode::PushConst(Value::Integer(2))));
+    assert!(matches!(bytecode[8], OpCode::Jump(4)));
+
+    let aligned = Compiler::compile("1 2 align 2 3").unwrap();
+    assert_eq!(aligned.len(), 3, "already aligned code is not padded");
+
+    let err = Compiler::compile("align 0").unwrap_err();
+    assert!(matches!(err, CompilerError::ParseError(_)));
+}
+
+#[test]
+fn streaming_compilation_rejects_forward_labels() {
+    let streamed = Compiler::compile_streaming("top: 1 Pop Jump top")
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert!(matches!(streamed[2], OpCode::Jump(0)));
+
+    let err = Compiler::compile_streaming("Jump later later: Return")
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_err();
+    assert!(matches!(err, CompilerError::InvalidAddress(l) if l == "later"));
+}
+
 #[test]
 fn invalid_token_returns_error() {
     let err = Compiler::compile("1 foo").unwrap_err();
@@ -137,3 +240,341 @@ async fn run_propagates_compiler_error() {
         VmError::CompilationError(CompilerError::InvalidToken(_))
     ));
 }
+
+#[test]
+fn entry_directive_sets_the_program_entry() {
+    let program = Compiler::compile_program("entry 4 1 Return 2 Return main: 3").unwrap();
+    assert_eq!(program.entry, 4);
+    assert_eq!(program.bytecode.len(), 5);
+
+    let program = Compiler::compile_program("helper: 1 Return main: 2 entry main").unwrap();
+    assert_eq!(program.entry, 2);
+
+    assert_eq!(Compiler::compile_program("1 2").unwrap().entry, 0);
+}
+
+#[test]
+fn entry_directive_rejects_bad_targets() {
+    let err = Compiler::compile_program("entry nowhere 1").unwrap_err();
+    assert!(matches!(err, CompilerError::InvalidAddress(a) if a == "nowhere"));
+
+    let err = Compiler::compile_program("entry 9 1").unwrap_err();
+    assert!(matches!(err, CompilerError::InvalidAddress(a) if a == "9"));
+
+    let err = Compiler::compile_program("entry 0 entry 1 1").unwrap_err();
+    assert!(matches!(err, CompilerError::ParseError(_)));
+}
+
+#[tokio::test]
+async fn vm_starts_at_the_program_entry() {
+    let program = Compiler::compile_program("entry 4 1 2 Add Return 10 20").unwrap();
+    let (mut vm, _tx) = VM::from_program(program, None);
+    assert_eq!(vm.ip(), 4);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &vec![Value::Integer(10), Value::Integer(20)]);
+}
+
+#[test]
+fn bare_words_are_rejected_by_default() {
+    let err = Compiler::compile("foo").unwrap_err();
+    assert!(matches!(err, CompilerError::InvalidToken(t) if t == "foo"));
+}
+
+#[test]
+fn bare_word_strings_option_compiles_words_to_strings() {
+    let options = CompilerOptions {
+        bare_word_strings: true,
+        ..CompilerOptions::default()
+    };
+    let bytecode = Compiler::compile_with_options("foo 1 Pop", options).unwrap();
+    assert_eq!(bytecode.len(), 3);
+    match bytecode[0] {
+        OpCode::PushString(text) => assert_eq!(&*text.as_str(), "foo"),
+        other => panic!("Expected PushString, got {other:?}"),
+    }
+    assert!(matches!(bytecode[2], OpCode::Pop));
+}
+
+#[tokio::test]
+async fn string_literals_push_heap_strings() {
+    let code = Compiler::compile("\"hello world\"").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    let Value::Reference(address) = vm.stack()[0] else {
+        panic!("Expected a string reference, got {:?}", vm.stack());
+    };
+    assert_eq!(
+        vm.heap().get(address).unwrap().summary(),
+        "String \"hello world\""
+    );
+}
+
+#[test]
+fn diagnostics_report_unreachable_code_after_return() {
+    let (bytecode, warnings) =
+        Compiler::compile_with_diagnostics("Call f Jump end f: 1 Return 2 Pop Return end:")
+            .unwrap();
+    assert_eq!(bytecode.len(), 7);
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].ip, 4);
+    assert!(warnings[0].message.contains("Unreachable"));
+}
+
+#[test]
+fn diagnostics_report_unreachable_code_after_halt() {
+    let (_, warnings) = Compiler::compile_with_diagnostics("1 Halt 2 Pop").unwrap();
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].ip, 2);
+    assert!(warnings[0].message.contains("Unreachable code after Halt"));
+
+    // A subroutine after a Halt is reached through its label.
+    let (_, warnings) = Compiler::compile_with_diagnostics("Call f Halt f: 1 Return").unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn diagnostics_report_a_self_jump() {
+    let (_, warnings) = Compiler::compile_with_diagnostics("spin: Jump spin").unwrap();
+    assert_eq!(
+        warnings,
+        vec![CompilerWarning {
+            ip: 0,
+            message: "Jump to itself loops forever".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn diagnostics_report_stored_but_unloaded_variables() {
+    let (_, warnings) =
+        Compiler::compile_with_diagnostics("1 StoreVar 0 2 StoreVar 1 LoadVar 1").unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].ip, 1);
+}
+
+#[test]
+fn clean_code_has_no_diagnostics() {
+    let source = "Switch 2 a b a: 1 Jump end b: 2 end: Return";
+    let (_, warnings) = Compiler::compile_with_diagnostics(source).unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+fn evaluated(source: &str) -> Vec<OpCode> {
+    let options = CompilerOptions {
+        evaluate_constants: true,
+        ..CompilerOptions::default()
+    };
+    Compiler::compile_with_options(source, options).unwrap()
+}
+
+#[test]
+fn evaluate_constants_reduces_pure_segments() {
+    let bytecode = evaluated("2 3 Mul 4 Add");
+    assert_eq!(bytecode.len(), 5);
+    assert!(matches!(bytecode[0], OpCode::PushConst(Value::Integer(10))));
+    assert!(bytecode[1..].iter().all(|op| matches!(op, OpCode::Nop)));
+}
+
+#[test]
+fn evaluate_constants_keeps_failing_segments() {
+    let bytecode = evaluated("1 0 Div");
+    assert_eq!(bytecode.len(), 3);
+    assert!(matches!(bytecode[2], OpCode::Div));
+}
+
+#[test]
+fn evaluate_constants_keeps_every_address() {
+    let bytecode = evaluated("2 3 Mul Jump end 5 end: 1 1 Add ReceiveMessage");
+    assert_eq!(bytecode.len(), 9);
+    assert!(matches!(bytecode[0], OpCode::PushConst(Value::Integer(6))));
+    assert!(matches!(bytecode[3], OpCode::Jump(5)));
+    assert!(matches!(bytecode[5], OpCode::PushConst(Value::Integer(2))));
+    assert!(matches!(bytecode[8], OpCode::ReceiveMessage));
+}
+
+#[tokio::test]
+async fn evaluate_constants_keeps_computed_jumps_working() {
+    let source = "2 3 Mul Pop 8 JumpDyn 0 Halt 42";
+    let (mut folded, _tx) = VM::new(evaluated(source), None);
+    folded.run().await.unwrap();
+    assert_eq!(folded.stack(), &[Value::Integer(42)]);
+}
+
+#[tokio::test]
+async fn evaluate_constants_keeps_aligned_addresses() {
+    let source = "1 2 3 Add Add align 8 CurrentIp";
+    let (mut folded, _tx) = VM::new(evaluated(source), None);
+    folded.run().await.unwrap();
+    assert_eq!(folded.stack(), &[Value::Integer(6), Value::Integer(8)]);
+}
+
+#[tokio::test]
+async fn evaluate_constants_preserves_results() {
+    let source = "1 2 Add Call double 10 Sub Jump end double: Dup Add Return end:";
+    let (mut plain, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    let (mut folded, _tx) = VM::new(evaluated(source), None);
+    plain.run().await.unwrap();
+    folded.run().await.unwrap();
+    assert_eq!(plain.stack(), folded.stack());
+}
+
+#[test]
+fn while_loop_compiles_to_conditional_jumps() {
+    let source = "0 StoreVar 0 while LoadVar 0 3 Sub ToBool do LoadVar 0 1 Add StoreVar 0 end";
+    let bytecode = Compiler::compile(source).unwrap();
+    assert_eq!(bytecode.len(), 12);
+    assert!(matches!(bytecode[2], OpCode::LoadVar(0)));
+    assert!(matches!(bytecode[6], OpCode::JumpIfFalse(12)));
+    assert!(matches!(bytecode[11], OpCode::Jump(2)));
+}
+
+#[tokio::test]
+async fn while_loop_runs_until_its_condition_fails() {
+    let source = "0 StoreVar 0 while LoadVar 0 3 Sub ToBool do LoadVar 0 1 Add StoreVar 0 end \
+                  LoadVar 0";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(3)]);
+}
+
+#[test]
+fn break_and_continue_target_the_innermost_loop() {
+    let source = "loop loop break end continue end";
+    let bytecode = Compiler::compile(source).unwrap();
+    assert_eq!(bytecode.len(), 4);
+    assert!(matches!(bytecode[0], OpCode::Jump(2)));
+    assert!(matches!(bytecode[1], OpCode::Jump(0)));
+    assert!(matches!(bytecode[2], OpCode::Jump(0)));
+    assert!(matches!(bytecode[3], OpCode::Jump(0)));
+}
+
+#[tokio::test]
+async fn break_leaves_a_loop() {
+    let source = "0 StoreVar 0 loop LoadVar 0 1 Add Dup StoreVar 0 4 Sub ToBool \
+                  JumpIfTrue again break again: end LoadVar 0";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(4)]);
+}
+
+#[test]
+fn loop_keywords_are_rejected_outside_loops() {
+    for source in [
+        "break",
+        "continue",
+        "end",
+        "1 do",
+        "loop 1",
+        "else",
+        "true if 1",
+    ] {
+        assert!(
+            matches!(Compiler::compile(source), Err(CompilerError::ParseError(_))),
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn if_without_else_jumps_past_its_body() {
+    let bytecode = Compiler::compile("true if 1 2 end 3").unwrap();
+    assert_eq!(bytecode.len(), 5);
+    assert!(matches!(bytecode[1], OpCode::JumpIfFalse(4)));
+}
+
+#[test]
+fn if_else_jumps_to_each_branch() {
+    let bytecode = Compiler::compile("false if 1 else 2 end 3").unwrap();
+    assert_eq!(bytecode.len(), 6);
+    assert!(matches!(bytecode[1], OpCode::JumpIfFalse(4)));
+    assert!(matches!(bytecode[3], OpCode::Jump(5)));
+}
+
+#[test]
+fn nested_ifs_get_their_own_targets() {
+    let source = "true if false if 1 else 2 end else 3 end";
+    let bytecode = Compiler::compile(source).unwrap();
+    assert_eq!(bytecode.len(), 9);
+    assert!(matches!(bytecode[1], OpCode::JumpIfFalse(8)));
+    assert!(matches!(bytecode[3], OpCode::JumpIfFalse(6)));
+    assert!(matches!(bytecode[5], OpCode::Jump(7)));
+    assert!(matches!(bytecode[7], OpCode::Jump(9)));
+}
+
+#[tokio::test]
+async fn if_else_runs_the_matching_branch() {
+    for (condition, expected) in [("true", 1), ("false", 2)] {
+        let source = format!("{condition} if 1 else 2 end");
+        let (mut vm, _tx) = VM::new(Compiler::compile(&source).unwrap(), None);
+        vm.run().await.unwrap();
+        assert_eq!(vm.stack(), &[Value::Integer(expected)]);
+    }
+}
+
+#[test]
+fn break_inside_if_leaves_the_enclosing_loop() {
+    let bytecode = Compiler::compile("loop true if break end end").unwrap();
+    assert_eq!(bytecode.len(), 4);
+    assert!(matches!(bytecode[1], OpCode::JumpIfFalse(3)));
+    assert!(matches!(bytecode[2], OpCode::Jump(4)));
+    assert!(matches!(bytecode[3], OpCode::Jump(0)));
+}
+
+#[tokio::test]
+async fn fn_defines_subroutines_for_call() {
+    let source = "fn double Dup Add end fn inc 1 Add end 3 Call double Call inc";
+    let bytecode = Compiler::compile(source).unwrap();
+    assert_eq!(bytecode.len(), 11);
+    assert!(matches!(bytecode[0], OpCode::Jump(4)));
+    assert!(matches!(bytecode[3], OpCode::Return));
+    assert!(matches!(bytecode[4], OpCode::Jump(8)));
+    assert!(matches!(bytecode[9], OpCode::Call(1)));
+    assert!(matches!(bytecode[10], OpCode::Call(5)));
+
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(7)]);
+}
+
+#[test]
+fn fn_requires_a_valid_name_and_an_end() {
+    for source in ["fn", "fn 1 end", "fn f 1"] {
+        assert!(
+            matches!(Compiler::compile(source), Err(CompilerError::ParseError(_))),
+            "{source}"
+        );
+    }
+    assert!(matches!(
+        Compiler::compile("loop fn f break end end"),
+        Err(CompilerError::ParseError(_))
+    ));
+}
+
+#[test]
+fn strict_mode_counts_tee_as_a_store() {
+    assert!(Compiler::compile_strict("1 Tee 0 LoadVar 0").is_ok());
+}
+
+#[test]
+fn strict_mode_rejects_update_before_store() {
+    let err = Compiler::compile_strict("1 UpdateVar 0").unwrap_err();
+    assert!(matches!(err, CompilerError::UndefinedVariable(0)));
+    assert!(Compiler::compile_strict("1 StoreVar 0 2 UpdateVar 0").is_ok());
+}
+
+#[test]
+fn layout_does_not_change_bytecode() {
+    let one_line = "5 StoreVar 0 top: LoadVar 0 ToBool JumpIfFalse done \
+                    LoadVar 0 1 - StoreVar 0 Jump top done: \"a b\" Print";
+    let layouts = [
+        "5 StoreVar 0\n\ntop:\n    LoadVar 0 ToBool\n    JumpIfFalse done\n\n    LoadVar 0 1 -\n    StoreVar 0\n    Jump top\ndone:\n\"a b\" Print\n",
+        "5 StoreVar 0\r\n\r\ntop:\r\n\tLoadVar 0 ToBool\r\n\tJumpIfFalse done\r\n\r\n\tLoadVar 0 1 -\r\n\tStoreVar 0\r\n\tJump top\r\ndone:\r\n\"a b\" Print\r\n",
+        "\t 5\tStoreVar\n0 top:\r\n \t\r\nLoadVar 0 ToBool JumpIfFalse\n\n\ndone LoadVar 0 1 - StoreVar 0 Jump top done: \"a b\"\r\nPrint",
+    ];
+
+    let expected = format!("{:?}", Compiler::compile(one_line).unwrap());
+    for source in layouts {
+        let bytecode = Compiler::compile(source).unwrap();
+        assert_eq!(format!("{bytecode:?}"), expected, "layout {source:?}");
+    }
+}
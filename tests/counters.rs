@@ -0,0 +1,46 @@
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+use raft::{RunResult, VM};
+
+#[tokio::test]
+async fn counter_inc_pushes_the_new_count() {
+    let source =
+        "NewCounter StoreVar 0 LoadVar 0 CounterInc LoadVar 0 CounterInc LoadVar 0 CounterGet";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(
+        vm.stack(),
+        &[Value::Integer(1), Value::Integer(2), Value::Integer(2)]
+    );
+}
+
+#[tokio::test]
+async fn counter_ops_reject_other_values() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("NewSet CounterInc").unwrap(), None);
+    assert!(matches!(vm.run().await, Err(VmError::InvalidReference)));
+}
+
+#[tokio::test]
+async fn forked_actors_share_a_counter() {
+    let source = "NewCounter StoreVar 0 Fork child Fork child Yield LoadVar 0 CounterGet Halt \
+                  child: 1000 StoreVar 1 \
+                  loop: LoadVar 0 CounterInc Pop LoadVar 1 1 Sub Tee 1 ToBool JumpIfTrue loop";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    assert_eq!(vm.run().await.unwrap(), RunResult::Yielded);
+
+    let [Value::Reference(first), Value::Reference(second)] = vm.stack()[..] else {
+        panic!("expected two children, got {:?}", vm.stack());
+    };
+    let mut tasks = Vec::new();
+    for address in [first, second] {
+        let (mut child, _child_tx) = vm.take_actor(address).unwrap();
+        tasks.push(tokio::spawn(async move { child.run().await }));
+    }
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+
+    assert_eq!(vm.run().await.unwrap(), RunResult::Halted);
+    assert_eq!(vm.stack().last(), Some(&Value::Integer(2000)));
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::vm;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use raft::vm::value::Value;
+use raft::vm::{RunResult, StepAction};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn step_observer_pauses_before_an_instruction() {
+    let mut vm = vm("1 2 Add 3 Mul");
+    vm.set_step_observer(Box::new(|ip, _| {
+        if ip == 2 {
+            StepAction::Pause
+        } else {
+            StepAction::Continue
+        }
+    }));
+
+    assert_eq!(vm.run().await.unwrap(), RunResult::Paused);
+    assert_eq!(vm.ip(), 2);
+    assert_eq!(vm.stack(), &[Value::Integer(1), Value::Integer(2)]);
+
+    // Resuming executes the paused instruction rather than pausing again.
+    assert_eq!(vm.run().await.unwrap(), RunResult::Completed);
+    assert_eq!(vm.stack(), &[Value::Integer(9)]);
+}
+
+#[tokio::test]
+async fn step_observer_sees_every_instruction() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    let mut vm = vm("1 2 Add");
+    vm.set_step_observer(Box::new(move |ip, opcode| {
+        log.lock().unwrap().push((ip, opcode.name()));
+        StepAction::Continue
+    }));
+
+    assert_eq!(vm.run().await.unwrap(), RunResult::Completed);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [(0, "PushConst"), (1, "PushConst"), (2, "Add")]
+    );
+}
+
+#[tokio::test]
+async fn pausing_on_every_instruction_single_steps() {
+    let mut vm = vm("1 2 Add");
+    vm.set_step_observer(Box::new(|_, _| StepAction::Pause));
+
+    let mut pauses = 0;
+    while vm.run().await.unwrap() == RunResult::Paused {
+        pauses += 1;
+    }
+    assert_eq!(pauses, 3);
+    assert_eq!(vm.stack(), &[Value::Integer(3)]);
+}
+
+#[tokio::test]
+async fn breakpoints_stop_the_run_until_cleared() {
+    let mut vm = vm("1 2 Add 3 Mul");
+    vm.set_breakpoints(HashSet::from([2]));
+
+    assert_eq!(vm.run().await.unwrap(), RunResult::BreakpointHit(2));
+    assert_eq!(vm.ip(), 2);
+    assert_eq!(vm.stack(), &[Value::Integer(1), Value::Integer(2)]);
+
+    vm.clear_breakpoints();
+    assert_eq!(vm.run().await.unwrap(), RunResult::Completed);
+    assert_eq!(vm.stack(), &[Value::Integer(9)]);
+}
+
+#[tokio::test]
+async fn breakpoints_can_be_moved_while_stopped() {
+    let mut vm = vm("1 2 Add 3 Mul");
+    vm.set_breakpoints(HashSet::from([2]));
+    assert_eq!(vm.run().await.unwrap(), RunResult::BreakpointHit(2));
+
+    // The breakpoint just hit is let through; the new one still stops.
+    vm.set_breakpoints(HashSet::from([2, 4]));
+    assert_eq!(vm.run().await.unwrap(), RunResult::BreakpointHit(4));
+    assert_eq!(vm.stack(), &[Value::Integer(3), Value::Integer(3)]);
+
+    assert_eq!(vm.run().await.unwrap(), RunResult::Completed);
+    assert_eq!(vm.stack(), &[Value::Integer(9)]);
+}
+
+#[tokio::test]
+async fn breakpoints_and_observers_stop_every_kind_of_run() {
+    let mut vm = vm("1 2 Add 3 Mul");
+    vm.set_breakpoints(HashSet::from([2]));
+    let result = vm.run_for(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(result, RunResult::BreakpointHit(2));
+
+    vm.clear_breakpoints();
+    vm.set_step_observer(Box::new(|ip, _| {
+        if ip == 4 {
+            StepAction::Pause
+        } else {
+            StepAction::Continue
+        }
+    }));
+    let result = vm.run_cancellable(CancellationToken::new()).await.unwrap();
+    assert_eq!(result, RunResult::Paused);
+    assert_eq!(vm.stack(), &[Value::Integer(3), Value::Integer(3)]);
+}
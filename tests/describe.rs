@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use raft::compiler::{Compiler, CompilerOptions};
+use raft::opcodes::{all_descriptions, description};
+use raft::vm::opcodes::OpCode;
+
+#[test]
+fn opcodes_describe_themselves() {
+    assert_eq!(OpCode::Add.name(), "Add");
+    assert!(!OpCode::Add.describe().is_empty());
+    assert_eq!(OpCode::Jump(4).name(), "Jump");
+    assert_eq!(description("Add"), Some(OpCode::Add.describe()));
+    assert_eq!(description("Frobnicate"), None);
+}
+
+#[test]
+fn every_opcode_has_a_distinct_description() {
+    let descriptions = all_descriptions();
+    let names: HashSet<_> = descriptions.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names.len(), descriptions.len(), "names are unique");
+    for (name, text) in &descriptions {
+        assert!(!text.is_empty(), "{name} has no description");
+    }
+}
+
+#[test]
+fn names_match_the_debug_form() {
+    let bytecode = Compiler::compile(
+        "1 StoreVar 0 \"s\" Switch 1 end Halt Fork end SafeDiv [1, 2] Spread end:",
+    )
+    .unwrap();
+    for opcode in bytecode {
+        let debug = format!("{opcode:?}");
+        assert!(debug.starts_with(opcode.name()), "{debug}");
+    }
+}
+
+/// The variant names declared in `pub enum OpCode`, read from the source so
+/// a new opcode cannot be added without also being described.
+fn declared_variants() -> HashSet<&'static str> {
+    let source = include_str!("../src/vm/opcodes.rs");
+    let body = source
+        .split_once("pub enum OpCode {")
+        .and_then(|(_, rest)| rest.split_once("\n}"))
+        .map(|(body, _)| body)
+        .expect("OpCode enum in src/vm/opcodes.rs");
+    body.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with(|c: char| c.is_ascii_uppercase()))
+        .map(|line| line.split(['(', ',', ' ']).next().unwrap())
+        .collect()
+}
+
+#[test]
+fn every_declared_opcode_is_described() {
+    let described: HashSet<_> = all_descriptions().iter().map(|(name, _)| *name).collect();
+    assert_eq!(described, declared_variants());
+}
+
+#[test]
+fn every_described_name_compiles_back_to_its_opcode() {
+    // Written as literals rather than by name.
+    let literals = ["PushConst", "PushString", "PushBigInt"];
+    let relative = CompilerOptions {
+        relative_jumps: true,
+        ..CompilerOptions::default()
+    };
+    for (name, _) in all_descriptions() {
+        let mnemonic = name.strip_suffix("Rel").unwrap_or(name);
+        let compiles = [
+            mnemonic.to_string(),
+            format!("{mnemonic} 0"),
+            format!("{mnemonic} 0 0"),
+        ]
+        .iter()
+        .flat_map(|source| [CompilerOptions::default(), relative].map(|o| (source, o)))
+        .any(|(source, options)| {
+            Compiler::compile_with_options(source, options)
+                .is_ok_and(|bytecode| bytecode.iter().any(|op| op.name() == name))
+        });
+        assert_eq!(compiles, !literals.contains(&name), "{name}");
+    }
+}
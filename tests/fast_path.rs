@@ -0,0 +1,59 @@
+use raft::compiler::Compiler;
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::Heap;
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+use std::time::Instant;
+
+/// A long arithmetic-only program: `x = (x * 3 + 7) % 1000` repeated.
+fn arithmetic_program(rounds: usize) -> Vec<OpCode> {
+    let source = format!("1 {}", "3 Mul 7 Add 1000 Mod ".repeat(rounds));
+    Compiler::compile(&source).unwrap()
+}
+
+#[test]
+fn only_channel_opcodes_are_async() {
+    assert!(OpCode::ReceiveMessage.is_async());
+    assert!(OpCode::SendMessage.is_async());
+    assert!(!OpCode::Add.is_async());
+    assert!(!OpCode::Jump(0).is_async());
+    assert!(!OpCode::SpawnActor(0).is_async());
+}
+
+#[tokio::test]
+async fn fast_path_matches_single_stepping() {
+    let code = arithmetic_program(500);
+
+    let mut execution = ExecutionContext::new(code.clone());
+    let mut heap = Heap::new();
+    let (_tx, mut mailbox) = tokio::sync::mpsc::channel(1);
+    while execution.ip < execution.bytecode.len() {
+        execution.step(&mut heap, &mut mailbox).await.unwrap();
+    }
+
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &execution.stack);
+}
+
+#[tokio::test]
+async fn fast_path_yields_only_for_async_opcodes() {
+    let code = Compiler::compile("2 3 Add ReceiveMessage Mul").unwrap();
+    let (mut vm, tx) = VM::new(code, None);
+    tx.send(Value::Integer(4)).await.unwrap();
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &vec![Value::Integer(20)]);
+}
+
+#[tokio::test]
+async fn long_arithmetic_program_runs_on_the_fast_path() {
+    let code = arithmetic_program(100_000);
+    let len = code.len();
+    let (mut vm, _tx) = VM::new(code, None);
+
+    let start = Instant::now();
+    vm.run().await.unwrap();
+    println!("{} arithmetic opcodes in {:?}", len, start.elapsed());
+    assert_eq!(vm.stack().len(), 1);
+}
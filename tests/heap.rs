@@ -0,0 +1,193 @@
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+use tokio::sync::mpsc::channel;
+
+#[tokio::test]
+async fn heap_size_reports_allocated_objects() {
+    let mut ctx = ExecutionContext::new(vec![]);
+    let mut heap = Heap::new();
+    let (_tx, mut rx) = channel(1);
+
+    OpCode::HeapSize
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+    assert_eq!(ctx.stack.pop(), Some(Value::Integer(0)));
+
+    heap.allocate(HeapObject::Array(vec![Value::Integer(1)], 1));
+    heap.allocate(HeapObject::Array(vec![], 0));
+
+    OpCode::HeapSize
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+    assert_eq!(ctx.stack.pop(), Some(Value::Integer(2)));
+
+    // The unreferenced array disappears once collected.
+    heap.collect_garbage();
+    OpCode::HeapSize
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+    assert_eq!(ctx.stack.pop(), Some(Value::Integer(1)));
+}
+
+#[tokio::test]
+async fn is_valid_reports_live_references_only() {
+    let mut ctx = ExecutionContext::new(vec![]);
+    let mut heap = Heap::new();
+    let (_tx, mut rx) = channel(1);
+    let live = heap.allocate(HeapObject::Array(vec![], 1));
+    let removed = heap.allocate(HeapObject::Array(vec![], 1));
+    heap.remove(removed);
+
+    ctx.stack = vec![
+        Value::Integer(1),
+        Value::Reference(removed),
+        Value::Reference(live),
+    ];
+    for _ in 0..3 {
+        OpCode::IsValid
+            .execute(&mut ctx, &mut heap, &mut rx)
+            .await
+            .unwrap();
+        let top = ctx.stack.pop().unwrap();
+        ctx.stack.insert(0, top);
+    }
+    assert_eq!(ctx.stack, [false, false, true].map(Value::Boolean).to_vec());
+    assert!(
+        !heap.get(live).unwrap().is_alive(),
+        "the popped reference is released"
+    );
+}
+
+#[test]
+fn reachable_from_follows_references_through_cycles() {
+    let mut heap = Heap::new();
+    let leaf = heap.allocate(HeapObject::String("leaf".to_string(), 1));
+    let inner = heap.allocate(HeapObject::Array(vec![Value::Reference(leaf)], 1));
+    let outer = heap.allocate(HeapObject::Array(
+        vec![Value::Integer(1), Value::Reference(inner)],
+        1,
+    ));
+    // `inner` points back at `outer`, closing a cycle.
+    if let Some(HeapObject::Array(items, _)) = heap.get_mut(inner) {
+        items.push(Value::Reference(outer));
+    }
+    let unrelated = heap.allocate(HeapObject::Array(vec![], 1));
+    let set = heap.allocate(HeapObject::Set(
+        [Value::Reference(unrelated)].into_iter().collect(),
+        1,
+    ));
+
+    let reachable = heap.reachable_from(&[Value::Reference(outer), Value::Null]);
+    assert_eq!(reachable, [outer, inner, leaf].into_iter().collect());
+
+    let reachable = heap.reachable_from(&[Value::Reference(set), Value::Reference(99)]);
+    assert_eq!(reachable, [set, unrelated].into_iter().collect());
+}
+
+#[test]
+fn presized_heap_holds_its_capacity_without_growing() {
+    let mut heap = Heap::with_capacity(64);
+    let capacity = heap.capacity();
+    assert!(capacity >= 64);
+    for i in 0..64 {
+        heap.allocate(HeapObject::Array(vec![Value::Integer(i)], 1));
+    }
+    assert_eq!(heap.capacity(), capacity);
+}
+
+#[test]
+fn vm_heap_capacity_is_threaded_through() {
+    let (vm, _tx) = VM::new(vec![], None);
+    let vm = vm.with_heap_capacity(128);
+    assert!(vm.heap().capacity() >= 128);
+}
+
+#[test]
+fn collected_addresses_are_reused() {
+    let mut heap = Heap::new();
+    let kept = heap.allocate(HeapObject::Array(vec![], 1));
+    let dropped = heap.allocate(HeapObject::Array(vec![], 0));
+    heap.collect_garbage();
+    assert!(heap.get(dropped).is_none());
+
+    let reused = heap.allocate(HeapObject::String("new".to_string(), 1));
+    assert_eq!(Heap::slot(reused), Heap::slot(dropped));
+    let fresh = heap.allocate(HeapObject::Array(vec![], 1));
+    assert_ne!(fresh, kept);
+    assert_ne!(fresh, reused);
+}
+
+#[test]
+fn restored_objects_displace_kept_objects_in_the_same_slot() {
+    let mut heap = Heap::new();
+    let array = heap.allocate(HeapObject::Array(vec![Value::Integer(1)], 1));
+    let snapshot = vec![(array, HeapObject::Array(vec![Value::Integer(1)], 1))];
+    if let Some(HeapObject::Array(_, rc)) = heap.get_mut(array) {
+        *rc = 0;
+    }
+    heap.collect_garbage();
+    // A channel, which a restore keeps, now occupies the array's old slot.
+    let (tx, rx) = channel(1);
+    let channel = heap.allocate(HeapObject::Channel(tx, rx, 1));
+    assert_eq!(Heap::slot(channel), Heap::slot(array));
+
+    heap.restore(snapshot);
+    assert_eq!(heap.len(), 1);
+    assert!(matches!(heap.get(array), Some(HeapObject::Array(..))));
+    assert!(heap.get(channel).is_none());
+    let next = heap.allocate(HeapObject::Array(vec![], 1));
+    assert_ne!(Heap::slot(next), Heap::slot(array));
+}
+
+#[test]
+fn slots_at_the_last_generation_are_retired_instead_of_wrapping() {
+    let slot_bits = usize::BITS / 2;
+    // Slot 0 at the highest generation its address can hold.
+    let last = usize::MAX << slot_bits;
+    assert_eq!(Heap::slot(last), 0);
+    let mut heap = Heap::new();
+    heap.restore([(last, HeapObject::Array(vec![], 0))]);
+    heap.collect_garbage();
+
+    // Reusing slot 0 would wrap its generation back to the address of its
+    // first occupant, so a fresh slot is used.
+    let next = heap.allocate(HeapObject::Array(vec![], 1));
+    assert_ne!(Heap::slot(next), 0);
+    assert!(heap.get(0).is_none());
+}
+
+#[test]
+#[should_panic(expected = "heap exhausted")]
+fn allocation_past_the_last_slot_panics() {
+    let last_slot = (1usize << (usize::BITS / 2)) - 1;
+    let mut heap = Heap::new();
+    heap.restore([(last_slot, HeapObject::Array(vec![], 1))]);
+    heap.allocate(HeapObject::Array(vec![], 1));
+}
+
+#[tokio::test]
+async fn stale_references_to_reused_slots_are_invalid() {
+    let mut heap = Heap::new();
+    let stale = heap.allocate(HeapObject::Array(vec![], 0));
+    heap.collect_garbage();
+    let current = heap.allocate(HeapObject::Array(vec![Value::Integer(1)], 1));
+
+    assert_ne!(stale, current);
+    assert!(heap.get(stale).is_none());
+    assert!(heap.get(current).is_some());
+
+    let mut ctx = ExecutionContext::new(vec![]);
+    ctx.stack.push(Value::Reference(stale));
+    let (_tx, mut rx) = channel(1);
+    OpCode::IsValid
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+    assert_eq!(ctx.stack.pop(), Some(Value::Boolean(false)));
+}
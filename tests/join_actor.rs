@@ -0,0 +1,42 @@
+use raft::compiler::Compiler;
+use raft::vm::heap::HeapObject;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+#[tokio::test]
+async fn join_actor_surfaces_a_child_fault() {
+    let code =
+        Compiler::compile("Jump main child: 1 0 Div main: SpawnActor child JoinActor").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    let fault = match vm.stack()[..] {
+        [Value::Reference(address)] => address,
+        ref other => panic!("Expected a fault reference, got {other:?}"),
+    };
+    match vm.heap().get(fault) {
+        Some(HeapObject::String(message, _)) => assert_eq!(message, "Division by zero"),
+        other => panic!("Expected fault string, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn join_actor_pushes_null_on_success() {
+    let code =
+        Compiler::compile("SpawnActor child JoinActor Jump end child: 1 2 Add end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &vec![Value::Null]);
+}
+
+#[tokio::test]
+async fn joined_child_sees_messages_sent_before_the_join() {
+    let code = Compiler::compile(
+        "SpawnActor child Dup 0 Swap SendMessage Pop JoinActor Jump end \
+         child: 10 ReceiveMessage Div end:",
+    )
+    .unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert!(matches!(vm.stack()[..], [Value::Reference(_)]));
+}
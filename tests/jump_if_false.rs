@@ -1,8 +1,13 @@
+mod common;
+
+use common::actor_ref_count;
+use raft::compiler::Compiler;
 use raft::vm::error::VmError;
 use raft::vm::execution::ExecutionContext;
-use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::heap::Heap;
 use raft::vm::opcodes::OpCode;
 use raft::vm::value::Value;
+use raft::vm::VM;
 use tokio::sync::mpsc::channel;
 
 #[tokio::test]
@@ -28,13 +33,6 @@ async fn jump_if_false_errors_on_empty_stack() {
     assert!(matches!(result, Err(VmError::StackUnderflow)));
 }
 
-fn actor_ref_count(heap: &Heap, address: usize) -> usize {
-    match heap.get(address) {
-        Some(HeapObject::Actor(_, _, rc)) => *rc,
-        _ => panic!("Expected actor at address {address}"),
-    }
-}
-
 #[tokio::test]
 async fn jump_if_false_skips_jump_when_true() {
     let mut ctx = ExecutionContext::new(vec![]);
@@ -54,7 +52,7 @@ async fn jump_if_false_skips_jump_when_true() {
 
 #[tokio::test]
 async fn jump_if_false_drops_reference_on_type_mismatch() {
-    let mut ctx = ExecutionContext::new(vec![]);
+    let mut ctx = ExecutionContext::new(vec![OpCode::Return]);
     let mut heap = Heap::new();
     let (_tx, mut rx) = channel(1);
 
@@ -81,3 +79,111 @@ async fn jump_if_false_drops_reference_on_type_mismatch() {
     heap.collect_garbage();
     assert!(heap.get(address).is_none());
 }
+
+async fn to_bool(value: Value) -> Value {
+    let mut ctx = ExecutionContext::new(vec![]);
+    ctx.stack.push(value);
+    let mut heap = Heap::new();
+    let (_tx, mut rx) = channel(1);
+    OpCode::ToBool
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+    ctx.stack.pop().unwrap()
+}
+
+#[tokio::test]
+async fn to_bool_follows_truthiness_rules() {
+    assert_eq!(to_bool(Value::Integer(0)).await, Value::Boolean(false));
+    assert_eq!(to_bool(Value::Integer(5)).await, Value::Boolean(true));
+    assert_eq!(to_bool(Value::Float(0.0)).await, Value::Boolean(false));
+    assert_eq!(to_bool(Value::Float(-0.5)).await, Value::Boolean(true));
+    assert_eq!(to_bool(Value::Null).await, Value::Boolean(false));
+    assert_eq!(to_bool(Value::Boolean(true)).await, Value::Boolean(true));
+}
+
+#[tokio::test]
+async fn to_bool_lets_jump_if_false_branch_on_integers() {
+    let bytecode = Compiler::compile("0 ToBool JumpIfFalse zero 1 Jump end zero: 2 end:").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(2)]);
+}
+
+#[tokio::test]
+async fn jump_if_true_jumps_when_true() {
+    let bytecode = Compiler::compile("true JumpIfTrue skip 1 skip: 2").unwrap();
+    assert!(matches!(bytecode[1], OpCode::JumpIfTrue(3)));
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(2)]);
+}
+
+#[tokio::test]
+async fn jump_if_true_falls_through_when_false() {
+    let bytecode = Compiler::compile("false JumpIfTrue skip 1 skip: 2").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(1), Value::Integer(2)]);
+}
+
+#[tokio::test]
+async fn jump_if_true_drops_reference_on_type_mismatch() {
+    let mut ctx = ExecutionContext::new(vec![OpCode::Return]);
+    let mut heap = Heap::new();
+    let (_tx, mut rx) = channel(1);
+
+    OpCode::SpawnActor(0)
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await
+        .unwrap();
+    let address = match ctx.stack.last().copied() {
+        Some(Value::Reference(addr)) => addr,
+        other => panic!("Expected actor reference on stack, got {other:?}"),
+    };
+
+    let result = OpCode::JumpIfTrue(0)
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await;
+
+    assert!(matches!(result, Err(VmError::TypeMismatch("JumpIfTrue"))));
+    assert!(ctx.stack.is_empty());
+    assert_eq!(actor_ref_count(&heap, address), 0);
+}
+
+#[tokio::test]
+async fn jump_if_true_checks_its_target() {
+    let mut ctx = ExecutionContext::new(vec![]);
+    ctx.stack.push(Value::Boolean(true));
+    let mut heap = Heap::new();
+    let (_tx, mut rx) = channel(1);
+    let result = OpCode::JumpIfTrue(5)
+        .execute(&mut ctx, &mut heap, &mut rx)
+        .await;
+    assert!(matches!(result, Err(VmError::ExecutionOutOfBounds)));
+}
+
+#[tokio::test]
+async fn jump_dyn_lands_on_a_computed_target() {
+    // 2 * 3 + 1 = 7 skips the 10 and lands on the 20.
+    let bytecode = Compiler::compile("2 3 Mul 1 Add JumpDyn 10 20").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(20)]);
+}
+
+#[tokio::test]
+async fn jump_dyn_rejects_targets_outside_the_bytecode() {
+    for source in ["-1 JumpDyn", "3 JumpDyn"] {
+        let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+        assert!(
+            matches!(vm.run().await, Err(VmError::ExecutionOutOfBounds)),
+            "{source}"
+        );
+    }
+    let (mut vm, _tx) = VM::new(Compiler::compile("true JumpDyn").unwrap(), None);
+    assert!(matches!(
+        vm.run().await,
+        Err(VmError::TypeMismatch("JumpDyn"))
+    ));
+}
@@ -0,0 +1,76 @@
+//! A target equal to the bytecode length is the address just past the last
+//! instruction; every opcode with an address operand accepts it.
+
+mod common;
+
+use common::run;
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+use raft::RunResult;
+
+#[tokio::test]
+async fn jump_to_the_end_completes() {
+    let (vm, result) = run("1 Jump end 2 end:").await;
+    assert_eq!(result.unwrap(), RunResult::Completed);
+    assert_eq!(vm.stack(), &[Value::Integer(1)]);
+}
+
+#[tokio::test]
+async fn conditional_jumps_to_the_end_complete() {
+    for source in [
+        "1 false JumpIfFalse end 2 end:",
+        "1 true JumpIfTrue end 2 end:",
+    ] {
+        let (vm, result) = run(source).await;
+        assert_eq!(result.unwrap(), RunResult::Completed, "{source}");
+        assert_eq!(vm.stack(), &[Value::Integer(1)], "{source}");
+    }
+}
+
+#[tokio::test]
+async fn call_to_the_end_completes() {
+    for source in ["1 Call end 2 end:", "1 TailCall end 2 end:"] {
+        let (vm, result) = run(source).await;
+        assert_eq!(result.unwrap(), RunResult::Completed, "{source}");
+        assert_eq!(vm.stack(), &[Value::Integer(1)], "{source}");
+    }
+}
+
+#[tokio::test]
+async fn actors_spawned_at_the_end_finish_at_once() {
+    for source in [
+        "SpawnActor end end:",
+        "SpawnSupervisor end end:",
+        "Fork end end:",
+    ] {
+        let (mut vm, result) = run(source).await;
+        assert_eq!(result.unwrap(), RunResult::Completed, "{source}");
+        let [Value::Reference(address)] = vm.stack()[..] else {
+            panic!("{source}: expected the actor, got {:?}", vm.stack());
+        };
+        if let Some((mut actor, _tx)) = vm.take_actor(address) {
+            assert_eq!(actor.run().await.unwrap(), RunResult::Completed, "{source}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn targets_past_the_end_are_rejected() {
+    for source in [
+        "Jump 2",
+        "false JumpIfFalse 3",
+        "true JumpIfTrue 3",
+        "Call 2",
+        "TailCall 2",
+        "SpawnActor 2",
+        "SpawnSupervisor 2",
+        "Fork 2",
+        "SpawnDetached 2",
+    ] {
+        let (_, result) = run(source).await;
+        assert!(
+            matches!(result, Err(VmError::ExecutionOutOfBounds)),
+            "{source}: {result:?}"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use raft::compiler::Compiler;
+use raft::linker::{LinkError, Linker};
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+#[tokio::test]
+async fn linked_module_calls_function_from_another_module() {
+    // The library skips over its own body so execution falls through to the
+    // module linked after it.
+    let library = Compiler::compile_module("math", "Jump _end double: 2 * Return _end:").unwrap();
+    let main = Compiler::compile_module("main", "21 Call double").unwrap();
+    assert_eq!(main.imports, vec![(1, "double".to_string())]);
+    assert!(!library.exports.contains_key("_end"));
+
+    let bytecode = Linker::new().add(library).add(main).link().unwrap();
+    assert_eq!(bytecode.len(), 6);
+    assert!(matches!(bytecode[0], OpCode::Jump(4)));
+    assert!(matches!(bytecode[5], OpCode::Call(1)));
+
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &vec![Value::Integer(42)]);
+}
+
+#[test]
+fn link_offsets_numeric_addresses() {
+    let first = Compiler::compile_module("first", "1 Pop").unwrap();
+    let second = Compiler::compile_module("second", "Jump 1 Return").unwrap();
+
+    let bytecode = Linker::new().add(first).add(second).link().unwrap();
+    assert!(matches!(bytecode[2], OpCode::Jump(3)));
+}
+
+#[test]
+fn unresolved_symbol_is_reported() {
+    let main = Compiler::compile_module("main", "Call missing").unwrap();
+    let err = Linker::new().add(main).link().unwrap_err();
+    assert!(matches!(
+        err,
+        LinkError::UnresolvedSymbol { module, symbol } if module == "main" && symbol == "missing"
+    ));
+}
+
+#[test]
+fn duplicate_symbol_is_reported() {
+    let a = Compiler::compile_module("a", "helper: Return").unwrap();
+    let b = Compiler::compile_module("b", "helper: Return").unwrap();
+    let err = Linker::new().add(a).add(b).link().unwrap_err();
+    assert!(matches!(
+        err,
+        LinkError::DuplicateSymbol { module, symbol } if module == "b" && symbol == "helper"
+    ));
+}
@@ -0,0 +1,33 @@
+use std::sync::{Arc, Mutex};
+
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+#[tokio::test]
+async fn message_hook_sees_received_messages() {
+    let (mut vm, tx) = VM::new(vec![OpCode::ReceiveMessage], None);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&seen);
+    vm.on_message(Box::new(move |message| log.lock().unwrap().push(*message)));
+
+    tx.send(Value::Integer(7)).await.unwrap();
+    vm.run().await.unwrap();
+
+    assert_eq!(*seen.lock().unwrap(), vec![Value::Integer(7)]);
+    assert_eq!(vm.stack(), &[Value::Integer(7)]);
+}
+
+#[tokio::test]
+async fn cleared_message_hook_is_not_called() {
+    let (mut vm, tx) = VM::new(vec![OpCode::ReceiveMessage], None);
+    let calls = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&calls);
+    vm.on_message(Box::new(move |_| *counter.lock().unwrap() += 1));
+    vm.clear_message_hook();
+
+    tx.send(Value::Boolean(true)).await.unwrap();
+    vm.run().await.unwrap();
+
+    assert_eq!(*calls.lock().unwrap(), 0);
+}
@@ -0,0 +1,64 @@
+use raft::compiler::Compiler;
+use raft::vm::value::Value;
+use raft::vm::VM;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// A cloneable in-memory writer for capturing VM output.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn print_and_warnings_go_to_the_configured_writers() {
+    let code = Compiler::compile("42 Print \"hi there\" Print [1, 2] Print true Print").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let (out, err) = (Capture::default(), Capture::default());
+    vm.set_output(out.clone());
+    vm.set_error_output(err.clone());
+    vm.run().await.unwrap();
+
+    assert_eq!(out.text(), "42\nhi there\n[1, 2]\ntrue\n");
+    assert_eq!(err.text(), "");
+    assert_eq!(vm.heap_ref_count(1), Some(0), "printed array is released");
+}
+
+#[tokio::test]
+async fn printing_a_dangling_reference_warns() {
+    let code = Compiler::compile("SpawnActor child Jump end Print child: 1 end:").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let (out, err) = (Capture::default(), Capture::default());
+    vm.set_output(out.clone());
+    vm.set_error_output(err.clone());
+    vm.run().await.unwrap();
+
+    // Detach the actor, then run the `Print` against the dangling reference.
+    let Value::Reference(address) = vm.stack()[0] else {
+        panic!("Expected actor reference, got {:?}", vm.stack());
+    };
+    vm.take_actor(address).unwrap();
+    vm.set_ip(2);
+    vm.run().await.unwrap();
+
+    assert_eq!(out.text(), "<invalid reference>\n");
+    assert_eq!(
+        err.text(),
+        format!("warning: Print of invalid reference {address}\n")
+    );
+}
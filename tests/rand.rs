@@ -0,0 +1,36 @@
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+async fn draw(source: &str, seed: u64) -> Result<Vec<Value>, VmError> {
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.set_seed(seed);
+    vm.run().await?;
+    Ok(vm.stack().clone())
+}
+
+#[tokio::test]
+async fn same_seed_draws_the_same_sequence() {
+    let source = "Rand Rand Rand 0 100 RandRange 0 100 RandRange";
+    let first = draw(source, 42).await.unwrap();
+    assert_eq!(first, draw(source, 42).await.unwrap());
+    assert_ne!(first, draw(source, 43).await.unwrap());
+}
+
+#[tokio::test]
+async fn rand_range_stays_within_bounds() {
+    let source = "-3 4 RandRange ".repeat(50);
+    for value in draw(&source, 7).await.unwrap() {
+        let Value::Integer(value) = value else {
+            panic!("expected integer, got {value:?}");
+        };
+        assert!((-3..4).contains(&value));
+    }
+}
+
+#[tokio::test]
+async fn rand_range_rejects_an_empty_range() {
+    let err = draw("5 5 RandRange", 1).await.unwrap_err();
+    assert!(matches!(err, VmError::EmptyRange(5, 5)));
+}
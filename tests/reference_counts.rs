@@ -1,3 +1,8 @@
+mod common;
+
+use common::actor_ref_count;
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
 use raft::vm::execution::ExecutionContext;
 use raft::vm::heap::{Heap, HeapObject};
 use raft::vm::opcodes::OpCode;
@@ -5,13 +10,6 @@ use raft::vm::value::Value;
 use raft::vm::vm::VM;
 use tokio::sync::mpsc::channel;
 
-fn actor_ref_count(heap: &Heap, address: usize) -> usize {
-    match heap.get(address) {
-        Some(HeapObject::Actor(_, _, rc)) => *rc,
-        _ => panic!("Expected actor at address {address}"),
-    }
-}
-
 #[tokio::test]
 async fn actor_reference_lifecycle_on_stack() {
     let mut execution = ExecutionContext::new(vec![OpCode::Return]);
@@ -157,3 +155,232 @@ async fn vm_pop_stack_drops_actor_reference() {
         "actor should be collected after reference count reaches zero"
     );
 }
+
+fn array_ref_count(heap: &Heap, address: usize) -> usize {
+    match heap.get(address) {
+        Some(HeapObject::Array(_, rc)) => *rc,
+        other => panic!("Expected array at address {address}, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn message_ownership_moves_through_the_mailbox_exactly_once() {
+    // Two contexts share a heap: `sender` sends an array to an actor whose
+    // mailbox `receiver` reads.
+    let mut heap = Heap::new();
+    let (tx, mut mailbox) = channel(1);
+    let (target, _) = VM::new(vec![OpCode::Return], None);
+    let actor = heap.allocate(HeapObject::Actor(target, tx, 0));
+
+    let mut sender = ExecutionContext::new(vec![OpCode::Return]);
+    let mut receiver = ExecutionContext::new(vec![OpCode::Return]);
+    let (_unused_tx, mut unused_mailbox) = channel(1);
+
+    for opcode in [
+        OpCode::NewArray,
+        OpCode::PushConst(Value::Reference(actor)),
+        OpCode::SendMessage,
+    ] {
+        opcode
+            .execute(&mut sender, &mut heap, &mut unused_mailbox)
+            .await
+            .unwrap();
+    }
+    let array = heap
+        .iter()
+        .find_map(|(address, object)| matches!(object, HeapObject::Array(..)).then_some(address))
+        .expect("array allocated");
+    assert_eq!(array_ref_count(&heap, array), 1, "owned by the mailbox");
+
+    OpCode::ReceiveMessage
+        .execute(&mut receiver, &mut heap, &mut mailbox)
+        .await
+        .unwrap();
+    assert_eq!(receiver.stack, vec![Value::Reference(array)]);
+    assert_eq!(array_ref_count(&heap, array), 1, "owned by the receiver");
+
+    // The sender dropping its actor reference does not affect the message.
+    OpCode::Pop
+        .execute(&mut sender, &mut heap, &mut unused_mailbox)
+        .await
+        .unwrap();
+    assert_eq!(array_ref_count(&heap, array), 1);
+
+    OpCode::Pop
+        .execute(&mut receiver, &mut heap, &mut mailbox)
+        .await
+        .unwrap();
+    heap.collect_garbage();
+    assert!(heap.get(array).is_none(), "no leaked count");
+}
+
+#[tokio::test]
+async fn ref_count_includes_the_inspected_reference() {
+    let code = Compiler::compile("NewArray Dup Dup RefCount").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack()[2], Value::Integer(3));
+    assert_eq!(vm.heap_ref_count(0), Some(2), "the operand was released");
+}
+
+#[tokio::test]
+async fn ref_count_rejects_non_references() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("1 RefCount").unwrap(), None);
+    let err = vm.run().await.expect_err("expected type mismatch");
+    assert!(matches!(err, VmError::TypeMismatch("RefCount")));
+}
+
+#[tokio::test]
+async fn clear_var_releases_a_local_reference() {
+    let bytecode = Compiler::compile("NewArray StoreVar 0 ClearVar 0 ClearVar 1").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack(), &[]);
+    assert_eq!(vm.heap_ref_count(0), Some(0));
+    vm.collect_garbage();
+    assert_eq!(
+        vm.heap_ref_count(0),
+        None,
+        "cleared array should be collected"
+    );
+}
+
+#[tokio::test]
+async fn tee_shares_a_reference_between_stack_and_local() {
+    let bytecode = Compiler::compile("NewArray Tee 0 LoadVar 0").unwrap();
+    assert!(matches!(bytecode[1], OpCode::Tee(0)));
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack(), &[Value::Reference(0), Value::Reference(0)]);
+    // Two stack copies and the local.
+    assert_eq!(vm.heap_ref_count(0), Some(3));
+}
+
+#[tokio::test]
+async fn tee_releases_the_value_it_overwrites() {
+    let bytecode = Compiler::compile("NewArray StoreVar 0 1 Tee 0").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(1)]);
+    assert_eq!(vm.heap_ref_count(0), Some(0));
+}
+
+#[tokio::test]
+async fn drain_mailbox_moves_queued_counts_into_the_array() {
+    let mut heap = Heap::new();
+    let mut ctx = ExecutionContext::new(vec![]);
+    let (tx, mut mailbox) = channel(4);
+    // The count a sender hands over with a queued reference.
+    let message = heap.allocate(HeapObject::Array(vec![], 1));
+    tx.send(Value::Reference(message)).await.unwrap();
+
+    OpCode::DrainMailbox
+        .execute(&mut ctx, &mut heap, &mut mailbox)
+        .await
+        .unwrap();
+
+    let Some(&Value::Reference(drained)) = ctx.stack.last() else {
+        panic!("expected an array reference, got {:?}", ctx.stack);
+    };
+    assert_eq!(array_ref_count(&heap, drained), 1);
+    assert_eq!(array_ref_count(&heap, message), 1, "owned by the array");
+}
+
+#[tokio::test]
+async fn drain_mailbox_releases_drained_messages_on_an_invalid_reference() {
+    let mut heap = Heap::new();
+    let mut ctx = ExecutionContext::new(vec![]);
+    let (tx, mut mailbox) = channel(4);
+    let message = heap.allocate(HeapObject::Array(vec![], 1));
+    tx.send(Value::Reference(message)).await.unwrap();
+    tx.send(Value::Reference(999)).await.unwrap();
+
+    let err = OpCode::DrainMailbox
+        .execute(&mut ctx, &mut heap, &mut mailbox)
+        .await
+        .expect_err("expected an invalid reference");
+
+    assert!(matches!(err, VmError::InvalidReference));
+    assert_eq!(array_ref_count(&heap, message), 0, "count released");
+    assert!(ctx.stack.is_empty());
+}
+
+#[tokio::test]
+async fn update_var_releases_the_array_it_replaces() {
+    let bytecode = Compiler::compile("NewArray StoreVar 0 NewArray UpdateVar 0").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack(), &[]);
+    assert_eq!(vm.heap_ref_count(0), Some(0), "old array released");
+    assert_eq!(vm.heap_ref_count(1), Some(1), "new array held by the local");
+}
+
+#[tokio::test]
+async fn update_var_requires_an_existing_local() {
+    let bytecode = Compiler::compile("NewArray UpdateVar 3").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    assert!(matches!(vm.run().await, Err(VmError::VariableNotFound(3))));
+}
+
+#[tokio::test]
+async fn ref_eq_compares_identity_and_releases_both_operands() {
+    let bytecode = Compiler::compile("NewArray Dup RefEq NewArray NewArray RefEq").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+
+    assert_eq!(vm.stack(), &[Value::Boolean(true), Value::Boolean(false)]);
+    for address in 0..3 {
+        assert_eq!(vm.heap_ref_count(address), Some(0));
+    }
+}
+
+#[tokio::test]
+async fn ref_eq_rejects_non_references() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("NewArray 1 RefEq").unwrap(), None);
+    assert!(matches!(
+        vm.run().await,
+        Err(VmError::TypeMismatch("RefEq"))
+    ));
+    assert_eq!(vm.stack().len(), 2);
+}
+
+#[tokio::test]
+async fn balanced_programs_have_no_leaks() {
+    let source = "NewArray StoreVar 0 LoadVar 0 1 ArrayPush Pop \
+                  NewSet 2 SetAdd Pop LoadVar 0 Pop ClearVar 0";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[]);
+    assert_eq!(vm.live_reference_count(), 0);
+    vm.assert_no_leaks().unwrap();
+}
+
+#[tokio::test]
+async fn references_still_held_are_not_leaks() {
+    let source = "NewArray StoreVar 0 LoadVar 0 NewArray ArrayPush NewArray";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    // The local and the stack share the outer array, which holds the inner.
+    assert_eq!(vm.live_reference_count(), 4);
+    vm.assert_no_leaks().unwrap();
+}
+
+#[tokio::test]
+async fn undelivered_messages_are_reported_as_leaks() {
+    // The actor never runs, so the array queued for it is never released.
+    let source = "NewArray SpawnActor end SendMessage Pop end:";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[]);
+    assert_eq!(vm.live_reference_count(), 1);
+    let err = vm.assert_no_leaks().unwrap_err();
+    assert!(matches!(err, VmError::AssertionFailed(_)));
+    assert!(
+        err.to_string().contains("has 1 references but 0 holders"),
+        "{err}"
+    );
+}
@@ -0,0 +1,62 @@
+use raft::compiler::{Compiler, CompilerOptions};
+use raft::vm::error::VmError;
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+const COUNTDOWN: &str = "5 StoreVar 0 \
+    top: LoadVar 0 ToBool JumpIfFalse done \
+    LoadVar 0 LoadVar 0 1 Sub StoreVar 0 Jump top \
+    done: true JumpIfTrue end 99 end: 7";
+
+fn relative(source: &str) -> Vec<OpCode> {
+    let options = CompilerOptions {
+        relative_jumps: true,
+        ..CompilerOptions::default()
+    };
+    Compiler::compile_with_options(source, options).unwrap()
+}
+
+async fn run(bytecode: Vec<OpCode>) -> Vec<Value> {
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    vm.stack().to_vec()
+}
+
+#[test]
+fn relative_jumps_option_emits_offsets() {
+    let bytecode = relative("Jump skip 1 skip: 2 JumpIfFalse 0");
+    assert!(matches!(bytecode[0], OpCode::JumpRel(2)));
+    assert!(matches!(bytecode[3], OpCode::JumpIfFalseRel(-3)));
+}
+
+#[tokio::test]
+async fn relative_program_matches_absolute_program() {
+    let absolute = Compiler::compile(COUNTDOWN).unwrap();
+    assert_eq!(run(relative(COUNTDOWN)).await, run(absolute).await);
+}
+
+#[tokio::test]
+async fn relative_program_runs_unchanged_after_moving() {
+    let mut moved = vec![OpCode::Nop; 3];
+    moved.extend(relative(COUNTDOWN));
+    let absolute = Compiler::compile(COUNTDOWN).unwrap();
+    assert_eq!(run(moved).await, run(absolute).await);
+}
+
+#[tokio::test]
+async fn relative_jumps_keep_switch_tables_absolute() {
+    let source = "1 Switch 2 a b 10 Jump end a: 20 Jump end b: 30 end:";
+    let bytecode = relative(source);
+    assert!(matches!(bytecode[2], OpCode::Jump(_)));
+    assert!(matches!(bytecode[3], OpCode::Jump(_)));
+    assert_eq!(run(bytecode).await, vec![Value::Integer(30)]);
+}
+
+#[tokio::test]
+async fn relative_jump_out_of_bounds_errors() {
+    for offset in [-1, 2] {
+        let (mut vm, _tx) = VM::new(vec![OpCode::JumpRel(offset)], None);
+        assert!(matches!(vm.run().await, Err(VmError::ExecutionOutOfBounds)));
+    }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use common::vm;
+use std::time::Duration;
+
+use raft::compiler::Compiler;
+use raft::vm::value::Value;
+use raft::vm::{RunResult, VmError, VM};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn running_off_the_end_completes() {
+    let mut vm = vm("1 2 Add");
+    assert_eq!(vm.run().await.unwrap(), RunResult::Completed);
+    assert_eq!(vm.stack(), &[Value::Integer(3)]);
+}
+
+#[tokio::test]
+async fn halt_stops_the_run() {
+    let mut vm = vm("1 Halt 2");
+    assert_eq!(vm.run().await.unwrap(), RunResult::Halted);
+    assert_eq!(vm.stack(), &[Value::Integer(1)]);
+    assert_eq!(vm.ip(), 2);
+}
+
+#[tokio::test]
+async fn yield_resumes_where_it_left_off() {
+    let mut vm = vm("1 Yield 2 Yield 3");
+    assert_eq!(vm.run().await.unwrap(), RunResult::Yielded);
+    assert_eq!(vm.stack(), &[Value::Integer(1)]);
+    assert_eq!(vm.run().await.unwrap(), RunResult::Yielded);
+    assert_eq!(vm.run().await.unwrap(), RunResult::Completed);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Integer(1), Value::Integer(2), Value::Integer(3)]
+    );
+}
+
+#[tokio::test]
+async fn run_for_times_out_while_waiting() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("ReceiveMessage").unwrap(), None);
+    let result = vm.run_for(Duration::from_millis(10)).await.unwrap();
+    assert_eq!(result, RunResult::TimedOut);
+}
+
+#[tokio::test]
+async fn run_for_reports_how_the_program_ended_in_time() {
+    let mut vm = vm("1 Halt");
+    let result = vm.run_for(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(result, RunResult::Halted);
+}
+
+#[tokio::test]
+async fn cancellable_run_reports_yield() {
+    let mut vm = vm("Yield 1");
+    let token = CancellationToken::new();
+    assert_eq!(
+        vm.run_cancellable(token.clone()).await.unwrap(),
+        RunResult::Yielded
+    );
+    assert_eq!(
+        vm.run_cancellable(token).await.unwrap(),
+        RunResult::Completed
+    );
+}
+
+#[tokio::test]
+async fn strict_run_rejects_leftover_values() {
+    assert_eq!(vm("5 3").run().await.unwrap(), RunResult::Completed);
+    assert!(matches!(
+        vm("5 3").run_strict().await,
+        Err(VmError::StackNotEmpty(2))
+    ));
+    assert!(matches!(
+        vm("5 3 Halt").run_strict().await,
+        Err(VmError::StackNotEmpty(2))
+    ));
+}
+
+#[tokio::test]
+async fn strict_run_allows_a_single_result() {
+    assert_eq!(
+        vm("5 3 Add").run_strict().await.unwrap(),
+        RunResult::Completed
+    );
+    assert_eq!(
+        vm("5 3 Yield Add").run_strict().await.unwrap(),
+        RunResult::Yielded
+    );
+}
+
+#[tokio::test]
+async fn bounded_run_yields_after_its_message_budget() {
+    let source = "top: ReceiveMessage Jump top";
+    let (mut vm, tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    for n in 1..=5 {
+        tx.send(Value::Integer(n)).await.unwrap();
+    }
+
+    assert_eq!(vm.run_bounded(2).await.unwrap(), RunResult::Yielded);
+    assert_eq!(vm.stack(), &[Value::Integer(1), Value::Integer(2)]);
+    assert_eq!(vm.mailbox.len(), 3);
+
+    assert_eq!(vm.run_bounded(2).await.unwrap(), RunResult::Yielded);
+    assert_eq!(vm.stack().len(), 4);
+}
+
+#[tokio::test]
+async fn bounded_run_reports_completion_within_budget() {
+    let (mut vm, tx) = VM::new(Compiler::compile("ReceiveMessage").unwrap(), None);
+    tx.send(Value::Integer(1)).await.unwrap();
+    assert_eq!(vm.run_bounded(3).await.unwrap(), RunResult::Completed);
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+#[tokio::test]
+async fn select_takes_the_first_channel_with_a_message() {
+    // Only the second channel has a message.
+    let source = "NewChannel StoreVar 0 NewChannel StoreVar 1 \
+                  7 LoadVar 1 SendMessage Pop \
+                  LoadVar 0 LoadVar 1 Collect 2 Select";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(7), Value::Integer(1)]);
+}
+
+#[tokio::test]
+async fn select_moves_a_queued_reference_onto_the_stack() {
+    let source = "NewChannel StoreVar 0 NewArray LoadVar 0 SendMessage Pop \
+                  LoadVar 0 Collect 1 Select Pop";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    let [Value::Reference(address)] = vm.stack()[..] else {
+        panic!("expected the received array, got {:?}", vm.stack());
+    };
+    assert_eq!(vm.heap_ref_count(address), Some(1));
+}
+
+#[tokio::test]
+async fn select_rejects_non_channels() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("1 Collect 1 Select").unwrap(), None);
+    assert!(matches!(
+        vm.run().await.unwrap_err(),
+        VmError::InvalidReference
+    ));
+}
+
+#[tokio::test]
+async fn channel_send_and_recv_round_trip_in_order() {
+    let source = "NewChannel StoreVar 0 \
+                  1 LoadVar 0 ChannelSend 2 LoadVar 0 ChannelSend \
+                  LoadVar 0 ChannelRecv LoadVar 0 ChannelRecv";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(1), Value::Integer(2)]);
+}
+
+#[tokio::test]
+async fn channel_recv_hands_the_queued_count_to_the_stack() {
+    let source = "NewChannel StoreVar 0 NewArray LoadVar 0 ChannelSend \
+                  LoadVar 0 ChannelRecv";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    let [Value::Reference(address)] = vm.stack()[..] else {
+        panic!("expected the received array, got {:?}", vm.stack());
+    };
+    assert_eq!(vm.heap_ref_count(address), Some(1));
+}
+
+#[tokio::test]
+async fn channel_recv_on_an_empty_channel_fails_instead_of_waiting() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("NewChannel ChannelRecv").unwrap(), None);
+    assert!(matches!(vm.run().await, Err(VmError::MailboxEmpty)));
+}
+
+/// Fills the channel in local 0 to its capacity of 100 with `SendMessage`.
+const FILL_CHANNEL: &str = "NewChannel StoreVar 0 100 StoreVar 1 \
+                            fill: 1 LoadVar 0 SendMessage Pop \
+                            LoadVar 1 1 Sub Tee 1 ToBool JumpIfTrue fill ";
+
+#[tokio::test]
+async fn send_message_fails_on_a_full_channel_instead_of_waiting() {
+    let source = format!("{FILL_CHANNEL} 2 LoadVar 0 SendMessage");
+    let (mut vm, _tx) = VM::new(Compiler::compile(&source).unwrap(), None);
+    let result = tokio::time::timeout(Duration::from_secs(5), vm.run())
+        .await
+        .expect("SendMessage waited on its own channel");
+    assert!(matches!(result, Err(VmError::MailboxFull(1))));
+}
+
+#[tokio::test]
+async fn send_if_alive_reports_a_full_channel_as_undelivered() {
+    let source = format!("{FILL_CHANNEL} NewArray LoadVar 0 SendIfAlive");
+    let (mut vm, _tx) = VM::new(Compiler::compile(&source).unwrap(), None);
+    tokio::time::timeout(Duration::from_secs(5), vm.run())
+        .await
+        .expect("SendIfAlive waited on its own channel")
+        .unwrap();
+    assert_eq!(vm.stack(), &[Value::Boolean(false)]);
+    vm.assert_no_leaks().unwrap();
+}
@@ -0,0 +1,67 @@
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+use tokio::sync::mpsc::channel;
+
+#[tokio::test]
+async fn send_all_delivers_a_batch_in_order() {
+    let source = "[1 2 3] SpawnActor child SendAll Jump end \
+                  child: ReceiveMessage ReceiveMessage ReceiveMessage end:";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+
+    let [Value::Reference(actor)] = vm.stack()[..] else {
+        panic!("expected the actor reference, got {:?}", vm.stack());
+    };
+    let (mut child, _sender) = vm.take_actor(actor).unwrap();
+    child.run().await.unwrap();
+    assert_eq!(
+        child.stack(),
+        &[Value::Integer(1), Value::Integer(2), Value::Integer(3)]
+    );
+}
+
+#[tokio::test]
+async fn send_all_sends_nothing_when_the_mailbox_would_overflow() {
+    let mut heap = Heap::new();
+    let (child, sender) = VM::new(vec![], None);
+    // Leave room for only two more messages.
+    for _ in 0..98 {
+        sender.try_send(Value::Null).unwrap();
+    }
+    let actor = heap.allocate(HeapObject::Actor(child, sender, 1));
+    let payload = heap.allocate(HeapObject::Array(vec![], 1));
+    let batch = heap.allocate(HeapObject::Array(
+        vec![
+            Value::Integer(1),
+            Value::Reference(payload),
+            Value::Integer(3),
+        ],
+        1,
+    ));
+
+    let mut execution = ExecutionContext::new(vec![]);
+    execution.stack = vec![Value::Reference(batch), Value::Reference(actor)];
+    let (_tx, mut mailbox) = channel(1);
+    let err = OpCode::SendAll
+        .execute(&mut execution, &mut heap, &mut mailbox)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, VmError::MailboxFull(3)));
+    assert_eq!(
+        execution.stack,
+        vec![Value::Reference(batch), Value::Reference(actor)]
+    );
+    for address in [actor, payload, batch] {
+        assert_eq!(heap.get(address).unwrap().ref_count(), 1);
+    }
+    let Some(HeapObject::Actor(_, sender, _)) = heap.get(actor) else {
+        panic!("expected the actor to stay on the heap");
+    };
+    assert_eq!(sender.capacity(), 2, "no message should have been queued");
+}
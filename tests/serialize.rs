@@ -0,0 +1,120 @@
+mod common;
+
+use common::run;
+use raft::vm::error::VmError;
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::{Value, MAX_DECODE_DEPTH};
+
+#[tokio::test]
+async fn arrays_travel_between_heaps_as_bytes() {
+    let (vm, result) = run("1 \"hi\" 2.5 true -3 Collect 3 Collect 3 Serialize").await;
+    result.unwrap();
+    let [Value::Reference(address)] = vm.stack()[..] else {
+        panic!("expected the bytes, got {:?}", vm.stack());
+    };
+    let Some(HeapObject::Bytes(bytes, _)) = vm.heap().get(address) else {
+        panic!("expected bytes at {}", address);
+    };
+
+    // "Send" the bytes to a VM with a heap of its own.
+    let mut heap = Heap::new();
+    let received = heap.allocate(HeapObject::Bytes(bytes.clone(), 1));
+    let mut execution = ExecutionContext::new(vec![OpCode::Deserialize]);
+    execution.stack.push(Value::Reference(received));
+    execution.run_sync(&mut heap).unwrap();
+
+    let [array] = execution.stack[..] else {
+        panic!("expected the array, got {:?}", execution.stack);
+    };
+    assert_eq!(array.display(&heap), "[1, \"hi\", [2.5, true, -3]]");
+    let Value::Reference(address) = array else {
+        panic!("expected a reference, got {:?}", array);
+    };
+    assert_eq!(heap.get(address).map(HeapObject::ref_count), Some(1));
+}
+
+#[tokio::test]
+async fn scalars_round_trip() {
+    let (vm, result) = run("-7 Serialize Deserialize").await;
+    result.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(-7)]);
+}
+
+#[tokio::test]
+async fn self_containing_arrays_are_rejected() {
+    let (_, result) = run("NewArray StoreVar 0 LoadVar 0 LoadVar 0 ArrayPush Serialize").await;
+    assert!(matches!(result, Err(VmError::CyclicValue)));
+}
+
+#[tokio::test]
+async fn other_objects_are_rejected() {
+    let (_, result) = run("NewSet Serialize").await;
+    assert!(matches!(result, Err(VmError::TypeMismatch("Serialize"))));
+}
+
+#[test]
+fn truncated_and_raw_reference_input_is_rejected() {
+    let mut heap = Heap::new();
+    let array = heap.allocate(HeapObject::Array(vec![Value::Integer(1)], 0));
+    let bytes = Value::Reference(array).serialize(&heap).unwrap();
+    assert!(matches!(
+        Value::deserialize(&bytes[..bytes.len() - 1], &mut heap),
+        Err(VmError::InvalidEncoding(_))
+    ));
+    assert!(matches!(
+        Value::deserialize(&Value::Reference(array).to_bytes(), &mut heap),
+        Err(VmError::InvalidEncoding(_))
+    ));
+}
+
+#[test]
+fn input_truncated_mid_array_leaves_the_heap_as_it_was() {
+    let mut heap = Heap::new();
+    let first = heap.allocate(HeapObject::String("first".to_string(), 1));
+    let inner = heap.allocate(HeapObject::Array(vec![Value::Reference(first)], 1));
+    let second = heap.allocate(HeapObject::String("second".to_string(), 1));
+    let array = heap.allocate(HeapObject::Array(
+        vec![Value::Reference(inner), Value::Reference(second)],
+        0,
+    ));
+    let bytes = Value::Reference(array).serialize(&heap).unwrap();
+    let before = heap.len();
+
+    assert!(matches!(
+        Value::deserialize(&bytes[..bytes.len() - 2], &mut heap),
+        Err(VmError::InvalidEncoding(_))
+    ));
+    assert_eq!(heap.len(), before);
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+        Value::deserialize(&trailing, &mut heap),
+        Err(VmError::InvalidEncoding(_))
+    ));
+    assert_eq!(heap.len(), before);
+}
+
+#[test]
+fn arrays_nested_too_deep_are_rejected() {
+    // Each level holds a string and then the next level.
+    let nested = |depth: usize| {
+        let mut bytes = Vec::new();
+        for _ in 0..depth {
+            bytes.extend([6, 2, 0, 0, 0, 5, 1, 0, 0, 0, b'x']);
+        }
+        bytes.extend([4]);
+        bytes
+    };
+    let mut heap = Heap::new();
+
+    let value = Value::deserialize(&nested(MAX_DECODE_DEPTH), &mut heap).unwrap();
+    assert!(matches!(value, Value::Reference(_)));
+    let before = heap.len();
+    assert!(matches!(
+        Value::deserialize(&nested(MAX_DECODE_DEPTH + 1), &mut heap),
+        Err(VmError::InvalidEncoding(_))
+    ));
+    assert_eq!(heap.len(), before);
+}
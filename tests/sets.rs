@@ -0,0 +1,115 @@
+mod common;
+
+use common::execute;
+use std::collections::HashSet;
+
+use raft::compiler::Compiler;
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+
+fn set_at(heap: &Heap, value: Option<&Value>) -> (HashSet<Value>, usize) {
+    match value {
+        Some(Value::Reference(addr)) => match heap.get(*addr) {
+            Some(HeapObject::Set(set, rc)) => (set.clone(), *rc),
+            other => panic!("Expected set at address {addr}, got {other:?}"),
+        },
+        other => panic!("Expected set reference, got {other:?}"),
+    }
+}
+
+fn build_set(items: &[i32]) -> Vec<OpCode> {
+    let mut code = vec![OpCode::NewSet];
+    for item in items {
+        code.push(OpCode::PushConst(Value::Integer(*item)));
+        code.push(OpCode::SetAdd);
+    }
+    code
+}
+
+#[tokio::test]
+async fn union_and_intersection_of_two_sets() {
+    let mut heap = Heap::new();
+
+    let mut ctx = ExecutionContext::new(vec![]);
+    execute(&mut ctx, &mut heap, &build_set(&[1, 2, 3])).await;
+    execute(&mut ctx, &mut heap, &build_set(&[2, 3, 4])).await;
+    execute(&mut ctx, &mut heap, &[OpCode::SetUnion]).await;
+    let (union, rc) = set_at(&heap, ctx.stack.last());
+    assert_eq!(rc, 1);
+    assert_eq!(union, [1, 2, 3, 4].map(Value::Integer).into());
+
+    let mut ctx = ExecutionContext::new(vec![]);
+    execute(&mut ctx, &mut heap, &build_set(&[1, 2, 3])).await;
+    execute(&mut ctx, &mut heap, &build_set(&[2, 3, 4])).await;
+    execute(&mut ctx, &mut heap, &[OpCode::SetIntersect]).await;
+    let (intersection, _) = set_at(&heap, ctx.stack.last());
+    assert_eq!(intersection, [2, 3].map(Value::Integer).into());
+    assert_eq!(ctx.stack.len(), 1);
+}
+
+#[tokio::test]
+async fn set_add_ignores_duplicates_and_set_has_reports_membership() {
+    let mut heap = Heap::new();
+    let mut ctx = ExecutionContext::new(vec![]);
+    execute(&mut ctx, &mut heap, &build_set(&[7, 7, 8])).await;
+    let (set, _) = set_at(&heap, ctx.stack.last());
+    assert_eq!(set.len(), 2);
+
+    execute(
+        &mut ctx,
+        &mut heap,
+        &[
+            OpCode::Dup,
+            OpCode::PushConst(Value::Integer(7)),
+            OpCode::SetHas,
+            OpCode::Swap,
+            OpCode::PushConst(Value::Integer(9)),
+            OpCode::SetHas,
+        ],
+    )
+    .await;
+    assert_eq!(ctx.stack, vec![Value::Boolean(true), Value::Boolean(false)]);
+}
+
+#[tokio::test]
+async fn sets_own_contained_references() {
+    let mut heap = Heap::new();
+    let mut ctx = ExecutionContext::new(vec![OpCode::Return]);
+    execute(
+        &mut ctx,
+        &mut heap,
+        &[
+            OpCode::NewSet,
+            OpCode::SpawnActor(0),
+            OpCode::SetAdd,
+            OpCode::NewSet,
+            OpCode::SetUnion,
+        ],
+    )
+    .await;
+
+    let (union, _) = set_at(&heap, ctx.stack.last());
+    let actor = *union.iter().next().expect("union should hold the actor");
+    let Value::Reference(actor_addr) = actor else {
+        panic!("Expected actor reference, got {actor:?}");
+    };
+    match heap.get(actor_addr) {
+        Some(HeapObject::Actor(_, _, rc)) => {
+            assert_eq!(*rc, 2, "both the original set and the union own the actor")
+        }
+        other => panic!("Expected actor, got {other:?}"),
+    }
+}
+
+#[test]
+fn compile_set_tokens() {
+    let bytecode = Compiler::compile("NewSet 1 SetAdd 1 SetHas SetUnion SetIntersect").unwrap();
+    assert_eq!(bytecode.len(), 7);
+    assert!(matches!(bytecode[0], OpCode::NewSet));
+    assert!(matches!(bytecode[2], OpCode::SetAdd));
+    assert!(matches!(bytecode[4], OpCode::SetHas));
+    assert!(matches!(bytecode[5], OpCode::SetUnion));
+    assert!(matches!(bytecode[6], OpCode::SetIntersect));
+}
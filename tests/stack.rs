@@ -0,0 +1,122 @@
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+#[tokio::test]
+async fn swap_n_exchanges_arbitrary_depths() {
+    let code = Compiler::compile("1 2 3 4 SwapN 0 3 SwapN 2 1").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(
+        vm.stack(),
+        &[4, 3, 2, 1].map(Value::Integer).to_vec(),
+        "depth 0 is the top of the stack"
+    );
+}
+
+#[tokio::test]
+async fn swap_n_with_same_depth_is_a_no_op() {
+    let code = Compiler::compile("1 2 SwapN 1 1").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[1, 2].map(Value::Integer).to_vec());
+}
+
+#[tokio::test]
+async fn swap_n_out_of_range_underflows() {
+    let code = Compiler::compile("1 2 SwapN 0 2").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected stack underflow");
+    assert!(matches!(err, VmError::StackUnderflow));
+}
+
+#[tokio::test]
+async fn current_ip_pushes_its_own_position() {
+    let code = Compiler::compile("CurrentIp 7 Pop CurrentIp Jump 6 Pop CurrentIp").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[0, 3, 6].map(Value::Integer).to_vec());
+}
+
+#[tokio::test]
+async fn nop_leaves_the_stack_untouched() {
+    let code = Compiler::compile("1 Nop Nop 2").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[1, 2].map(Value::Integer).to_vec());
+}
+
+#[tokio::test]
+async fn bounded_stack_overflows_without_reallocating() {
+    let code = Compiler::compile("1 2 3 4 5").unwrap();
+    let (vm, _tx) = VM::new(code, None);
+    let mut vm = vm.with_stack_capacity(4);
+    let capacity = vm.stack().capacity();
+    assert!(capacity >= 4);
+
+    let err = vm.run().await.expect_err("expected stack overflow");
+    assert!(matches!(err, VmError::StackOverflow));
+    assert_eq!(vm.stack(), &[1, 2, 3, 4].map(Value::Integer).to_vec());
+    assert_eq!(vm.stack().capacity(), capacity);
+}
+
+#[tokio::test]
+async fn bounded_stack_allows_pushes_up_to_the_limit() {
+    let code = Compiler::compile("1 2 Add 3 4").unwrap();
+    let (vm, _tx) = VM::new(code, None);
+    let mut vm = vm.with_stack_capacity(3);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[3, 3, 4].map(Value::Integer).to_vec());
+}
+
+#[tokio::test]
+async fn expect_depth_accepts_a_balanced_call() {
+    let bytecode = Compiler::compile("1 Call f ExpectDepth 2 Jump end f: 2 Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(1), Value::Integer(2)]);
+}
+
+#[tokio::test]
+async fn expect_depth_rejects_an_unbalanced_call() {
+    let bytecode = Compiler::compile("1 Call f ExpectDepth 2 Jump end f: 2 3 Return end:").unwrap();
+    let (mut vm, _tx) = VM::new(bytecode, None);
+    let err = vm.run().await.unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::UnexpectedDepth {
+            expected: 2,
+            actual: 3
+        }
+    ));
+}
+
+#[tokio::test]
+async fn cmov_selects_by_condition() {
+    for (condition, expected) in [("true", 1), ("false", 2)] {
+        let source = format!("1 2 {condition} CMov");
+        let (mut vm, _tx) = VM::new(Compiler::compile(&source).unwrap(), None);
+        vm.run().await.unwrap();
+        assert_eq!(vm.stack(), &[Value::Integer(expected)]);
+    }
+}
+
+#[tokio::test]
+async fn cmov_releases_the_discarded_reference() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("NewArray 7 false CMov").unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Integer(7)]);
+    assert_eq!(vm.heap_ref_count(0), Some(0));
+
+    let (mut vm, _tx) = VM::new(Compiler::compile("NewArray 7 true CMov").unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Reference(0)]);
+    assert_eq!(vm.heap_ref_count(0), Some(1));
+}
+
+#[tokio::test]
+async fn cmov_requires_a_boolean_condition() {
+    let (mut vm, _tx) = VM::new(Compiler::compile("1 2 3 CMov").unwrap(), None);
+    assert!(matches!(vm.run().await, Err(VmError::TypeMismatch("CMov"))));
+}
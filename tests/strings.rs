@@ -0,0 +1,58 @@
+mod common;
+
+use common::run;
+use raft::vm::error::VmError;
+use raft::vm::heap::HeapObject;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+fn object(vm: &VM, value: Value) -> &HeapObject {
+    match value {
+        Value::Reference(address) => vm.heap().get(address).expect("live object"),
+        other => panic!("Expected reference, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn str_code_points_splits_a_string_into_characters() {
+    let (vm, result) = run("\"Hi!\" StrCodePoints").await;
+    result.unwrap();
+    match object(&vm, vm.stack()[0]) {
+        HeapObject::Array(codes, _) => {
+            assert_eq!(codes, &[72, 105, 33].map(Value::Integer).to_vec())
+        }
+        other => panic!("Expected array, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn ascii_string_round_trips_through_code_points() {
+    let (vm, result) = run("\"hello, raft\" StrCodePoints ArrayToStr").await;
+    result.unwrap();
+    match object(&vm, vm.stack()[0]) {
+        HeapObject::String(text, rc) => {
+            assert_eq!(text, "hello, raft");
+            assert_eq!(*rc, 1);
+        }
+        other => panic!("Expected string, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn array_to_str_rejects_invalid_code_points() {
+    let (_, result) = run("[104, 55296] ArrayToStr").await;
+    assert!(matches!(result, Err(VmError::InvalidCodePoint(55296))));
+
+    let (_, result) = run("[-1] ArrayToStr").await;
+    assert!(matches!(result, Err(VmError::InvalidCodePoint(-1))));
+}
+
+#[tokio::test]
+async fn version_pushes_the_crate_version() {
+    let (vm, result) = run("Version").await;
+    result.unwrap();
+    match object(&vm, vm.stack()[0]) {
+        HeapObject::String(text, _) => assert_eq!(text, raft::VERSION),
+        other => panic!("Expected string, got {other:?}"),
+    }
+}
@@ -0,0 +1,72 @@
+use raft::compiler::Compiler;
+use raft::vm::error::VmError;
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+
+const DISPATCH: &str = "Switch 3 zero one two \
+                        -1 Jump end \
+                        zero: 100 Jump end \
+                        one: 101 Jump end \
+                        two: 102 \
+                        end:";
+
+async fn dispatch(index: i32) -> Vec<Value> {
+    let code = Compiler::compile(&format!("{index} {DISPATCH}")).unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    vm.stack().clone()
+}
+
+#[test]
+fn switch_compiles_to_inline_jump_table() {
+    let bytecode = Compiler::compile(DISPATCH).unwrap();
+    assert!(matches!(bytecode[0], OpCode::Switch(3)));
+    assert!(matches!(bytecode[1], OpCode::Jump(6)));
+    assert!(matches!(bytecode[2], OpCode::Jump(8)));
+    assert!(matches!(bytecode[3], OpCode::Jump(10)));
+}
+
+#[tokio::test]
+async fn switch_dispatches_to_each_arm() {
+    assert_eq!(dispatch(0).await, vec![Value::Integer(100)]);
+    assert_eq!(dispatch(1).await, vec![Value::Integer(101)]);
+    assert_eq!(dispatch(2).await, vec![Value::Integer(102)]);
+}
+
+#[tokio::test]
+async fn switch_falls_through_when_out_of_range() {
+    assert_eq!(dispatch(3).await, vec![Value::Integer(-1)]);
+    assert_eq!(dispatch(-5).await, vec![Value::Integer(-1)]);
+}
+
+#[tokio::test]
+async fn switch_rejects_non_integer_index() {
+    let code = Compiler::compile(&format!("true {DISPATCH}")).unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected type mismatch");
+    assert!(matches!(err, VmError::TypeMismatch("Switch")));
+}
+
+#[tokio::test]
+async fn switch_rejects_malformed_table() {
+    let code = vec![
+        OpCode::PushConst(Value::Integer(0)),
+        OpCode::Switch(1),
+        OpCode::Return,
+    ];
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected malformed table");
+    assert!(matches!(err, VmError::MalformedSwitchTable(2)));
+}
+
+#[tokio::test]
+async fn switch_rejects_a_table_past_the_end_of_the_address_space() {
+    let code = vec![
+        OpCode::PushConst(Value::Integer(0)),
+        OpCode::Switch(usize::MAX),
+    ];
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected out of bounds");
+    assert!(matches!(err, VmError::ExecutionOutOfBounds));
+}
@@ -0,0 +1,74 @@
+use raft::compiler::Compiler;
+use raft::vm::execution::ExecutionContext;
+use raft::vm::heap::Heap;
+use raft::vm::opcodes::OpCode;
+use raft::vm::value::Value;
+use raft::vm::VM;
+use tokio::sync::mpsc::channel;
+
+/// Builds a program that recurses once per `true` on the stack, using either
+/// `Call` or `TailCall` for the recursive step.
+fn countdown(depth: usize, recurse: fn(usize) -> OpCode) -> Vec<OpCode> {
+    let mut code = vec![OpCode::PushConst(Value::Boolean(false))];
+    code.extend(std::iter::repeat_n(
+        OpCode::PushConst(Value::Boolean(true)),
+        depth,
+    ));
+    let function = code.len() + 2;
+    code.push(OpCode::Call(function));
+    code.push(OpCode::Jump(function + 3));
+    code.push(OpCode::JumpIfFalse(function + 2));
+    code.push(recurse(function));
+    code.push(OpCode::Return);
+    code
+}
+
+async fn max_call_depth(code: Vec<OpCode>) -> usize {
+    let mut ctx = ExecutionContext::new(code);
+    let mut heap = Heap::new();
+    let (_tx, mut rx) = channel(1);
+    let mut max_depth = 0;
+    while ctx.ip < ctx.bytecode.len() {
+        ctx.step(&mut heap, &mut rx).await.unwrap();
+        max_depth = max_depth.max(ctx.call_stack.len());
+    }
+    assert!(ctx.stack.is_empty());
+    assert!(ctx.call_stack.is_empty());
+    max_depth
+}
+
+#[tokio::test]
+async fn tail_recursive_countdown_keeps_call_stack_constant() {
+    assert_eq!(max_call_depth(countdown(10_000, OpCode::TailCall)).await, 1);
+}
+
+#[tokio::test]
+async fn plain_recursion_grows_call_stack() {
+    assert_eq!(max_call_depth(countdown(100, OpCode::Call)).await, 101);
+}
+
+#[test]
+fn compile_tail_call_token() {
+    let bytecode = Compiler::compile("loop: TailCall loop TailCall 0").unwrap();
+    assert!(matches!(bytecode[0], OpCode::TailCall(0)));
+    assert!(matches!(bytecode[1], OpCode::TailCall(0)));
+}
+
+#[tokio::test]
+async fn call_depth_reports_nesting_level() {
+    let source = "CallDepth Call outer Jump end \
+                  outer: CallDepth Call inner Return \
+                  inner: CallDepth TailCall leaf \
+                  leaf: CallDepth Return end:";
+    let (mut vm, _tx) = VM::new(Compiler::compile(source).unwrap(), None);
+    vm.run().await.unwrap();
+    assert_eq!(
+        vm.stack(),
+        &[
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Integer(2),
+            Value::Integer(2),
+        ]
+    );
+}
@@ -0,0 +1,36 @@
+use raft::vm::error::VmError;
+use raft::vm::value::Value;
+
+fn round_trip(value: Value) -> Value {
+    Value::from_bytes(&value.to_bytes()).unwrap()
+}
+
+#[test]
+fn scalars_round_trip_through_bytes() {
+    for value in [
+        Value::Integer(0),
+        Value::Integer(i32::MIN),
+        Value::Integer(i32::MAX),
+        Value::Float(-2.5),
+        Value::Float(f64::INFINITY),
+        Value::Boolean(true),
+        Value::Boolean(false),
+        Value::Reference(42),
+        Value::Null,
+    ] {
+        assert_eq!(round_trip(value), value);
+    }
+}
+
+#[test]
+fn nan_round_trips_as_nan() {
+    assert!(matches!(round_trip(Value::Float(f64::NAN)), Value::Float(f) if f.is_nan()));
+}
+
+#[test]
+fn malformed_bytes_are_rejected() {
+    for bytes in [&[][..], &[0, 1, 2], &[2, 7], &[4, 0], &[99]] {
+        let err = Value::from_bytes(bytes).unwrap_err();
+        assert!(matches!(err, VmError::InvalidEncoding(_)), "{bytes:?}");
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::HashSet;
+
+use raft::vm::heap::{Heap, HeapObject};
+use raft::vm::value::Value;
+
+#[test]
+fn scalars_render_directly() {
+    let heap = Heap::new();
+    assert_eq!(Value::Integer(-3).display(&heap), "-3");
+    assert_eq!(Value::Float(2.5).display(&heap), "2.5");
+    assert_eq!(Value::Boolean(true).display(&heap), "true");
+    assert_eq!(Value::Null.display(&heap), "null");
+    assert_eq!(Value::Reference(9).display(&heap), "<invalid reference>");
+}
+
+#[test]
+fn references_render_their_contents() {
+    let mut heap = Heap::new();
+    let items = (1..=3).map(Value::Integer).collect();
+    let array = heap.allocate(HeapObject::Array(items, 1));
+    assert_eq!(Value::Reference(array).display(&heap), "[1, 2, 3]");
+
+    let text = heap.allocate(HeapObject::String("hi".to_string(), 1));
+    assert_eq!(Value::Reference(text).display(&heap), "hi");
+    let nested = heap.allocate(HeapObject::Array(
+        vec![Value::Reference(text), Value::Reference(array), Value::Null],
+        1,
+    ));
+    assert_eq!(
+        Value::Reference(nested).display(&heap),
+        "[\"hi\", [1, 2, 3], null]"
+    );
+
+    let set = heap.allocate(HeapObject::Set(
+        HashSet::from([Value::Integer(2), Value::Integer(1)]),
+        1,
+    ));
+    assert_eq!(Value::Reference(set).display(&heap), "{1, 2}");
+}
+
+#[test]
+fn cycles_are_cut_short() {
+    let mut heap = Heap::new();
+    let array = heap.allocate(HeapObject::Array(vec![Value::Integer(1)], 1));
+    let Some(HeapObject::Array(items, _)) = heap.get_mut(array) else {
+        unreachable!();
+    };
+    items.push(Value::Reference(array));
+    assert_eq!(Value::Reference(array).display(&heap), "[1, [...]]");
+}
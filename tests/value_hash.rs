@@ -0,0 +1,49 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use raft::vm::value::Value;
+
+fn hash_of(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn integer_and_boolean_keys_round_trip() {
+    let mut map = HashMap::new();
+    map.insert(Value::Integer(1), "one");
+    map.insert(Value::Integer(-7), "minus seven");
+    map.insert(Value::Boolean(true), "yes");
+    map.insert(Value::Boolean(false), "no");
+
+    assert_eq!(map.get(&Value::Integer(1)), Some(&"one"));
+    assert_eq!(map.get(&Value::Integer(-7)), Some(&"minus seven"));
+    assert_eq!(map.get(&Value::Boolean(true)), Some(&"yes"));
+    assert_eq!(map.get(&Value::Boolean(false)), Some(&"no"));
+    assert_eq!(map.get(&Value::Integer(2)), None);
+}
+
+#[test]
+fn variants_with_same_payload_are_distinct_keys() {
+    let mut map = HashMap::new();
+    map.insert(Value::Integer(0), 'i');
+    map.insert(Value::Reference(0), 'r');
+    map.insert(Value::Null, 'n');
+
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get(&Value::Integer(0)), Some(&'i'));
+    assert_eq!(map.get(&Value::Reference(0)), Some(&'r'));
+    assert_eq!(map.get(&Value::Null), Some(&'n'));
+}
+
+#[test]
+fn float_hash_is_consistent_with_equality() {
+    assert_eq!(Value::Float(0.0), Value::Float(-0.0));
+    assert_eq!(hash_of(&Value::Float(0.0)), hash_of(&Value::Float(-0.0)));
+    assert_eq!(hash_of(&Value::Float(1.5)), hash_of(&Value::Float(1.5)));
+
+    assert!(Value::Float(1.5).is_hashable());
+    assert!(!Value::Float(f64::NAN).is_hashable());
+}
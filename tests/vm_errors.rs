@@ -1,4 +1,6 @@
+use raft::compiler::Compiler;
 use raft::vm::{error::VmError, opcodes::OpCode, value::Value, vm::VM};
+use tokio::sync::mpsc::error::SendError;
 
 #[tokio::test]
 async fn division_by_zero_returns_error() {
@@ -88,3 +90,65 @@ async fn spawn_supervisor_out_of_bounds_returns_error() {
         .expect_err("expected execution out of bounds for spawn supervisor");
     assert!(matches!(err, VmError::ExecutionOutOfBounds));
 }
+
+#[tokio::test]
+async fn failed_assert_returns_error() {
+    let code = Compiler::compile("false Assert").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected assertion failure");
+    assert!(matches!(err, VmError::AssertionFailed(msg) if msg == "at instruction 1"));
+
+    let code = Compiler::compile("false Assert \"odd input\"").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected assertion failure");
+    assert_eq!(err.to_string(), "Assertion failed: odd input");
+}
+
+#[tokio::test]
+async fn passing_assert_is_a_no_op() {
+    let code = Compiler::compile("true Assert").unwrap();
+    let (mut vm, _tx) = VM::new(code, None);
+    vm.run().await.unwrap();
+    assert!(vm.stack().is_empty());
+}
+
+#[tokio::test]
+async fn assert_on_non_boolean_is_a_type_mismatch() {
+    let code = vec![OpCode::PushConst(Value::Integer(1)), OpCode::Assert(None)];
+    let (mut vm, _tx) = VM::new(code, None);
+    let err = vm.run().await.expect_err("expected type mismatch");
+    assert!(matches!(err, VmError::TypeMismatch("Assert")));
+}
+
+#[test]
+fn channel_send_error_keeps_the_undelivered_value() {
+    let err = VmError::from(SendError(Value::Integer(7)));
+    assert_eq!(err.to_string(), "Channel send error: channel closed");
+    assert!(matches!(
+        err,
+        VmError::ChannelSend {
+            ref error,
+            value: Value::Integer(7),
+        } if error == "channel closed"
+    ));
+}
+
+#[tokio::test]
+async fn receive_after_all_senders_drop_reports_a_closed_mailbox() {
+    let (mut vm, tx) = VM::new(vec![OpCode::ReceiveMessage], None);
+    drop(tx);
+    let err = vm.run().await.unwrap_err();
+    assert!(matches!(err, VmError::MailboxClosed), "got {err:?}");
+}
+
+#[tokio::test]
+async fn unset_locals_error_unless_defaulted_to_null() {
+    let code = Compiler::compile("LoadVar 0").unwrap();
+    let (mut vm, _tx) = VM::new(code.clone(), None);
+    assert!(matches!(vm.run().await, Err(VmError::VariableNotFound(0))));
+
+    let (vm, _tx) = VM::new(code, None);
+    let mut vm = vm.with_default_locals_to_null(true);
+    vm.run().await.unwrap();
+    assert_eq!(vm.stack(), &[Value::Null]);
+}